@@ -0,0 +1,559 @@
+use std::fs;
+use std::path::Path;
+
+use crate::diff::Diff;
+use crate::error::{ApplyErr, ApplyErrKind};
+use crate::file_diff::FileDiff;
+use crate::hunk::{self, Hunk};
+use crate::macros::debugln;
+use crate::path::FilePath;
+
+// How far from its position a hunk is looked for unless told otherwise,
+// e.g. after lines were added or removed above it since the diff was made
+pub const DEFAULT_MAX_OFFSET: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub reverse: bool, // undo the diff, going from the '+' to the '-' side
+    pub dry_run: bool, // check the hunks without writing any file
+    // how many lines away from the position in its header a hunk may be
+    // found, after the offset of the previous hunk
+    pub max_offset: usize,
+    // how many context lines of a hunk may differ from the file, which
+    // then keeps its own version of them
+    pub fuzz: usize,
+    // write hunks that fail into the file as conflict blocks
+    pub conflicts: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            reverse: false,
+            dry_run: false,
+            max_offset: DEFAULT_MAX_OFFSET,
+            fuzz: 0,
+            conflicts: false,
+        }
+    }
+}
+
+// What became of one hunk
+#[derive(Debug)]
+pub struct Report {
+    pub file_name: FilePath,
+    pub header: hunk::Header,
+    pub result: Result<(), ApplyErr>,
+    pub offset: i64,    // lines from the position in the header
+    pub fuzz: usize,    // context lines that didn't match
+    pub conflict: bool, // written as a conflict block
+}
+
+impl Report {
+    // The report as tab separated fields: file name, the four header
+    // fields, status (applied, conflict or failed), offset and fuzz
+    pub fn record(&self) -> String {
+        let status = match (&self.result, self.conflict) {
+            (Ok(()), _) => "applied",
+            (Err(_), true) => "conflict",
+            (Err(_), false) => "failed",
+        };
+        let [old_start, old_count, new_start, new_count] = self.header.fields;
+        format!(
+            "{}\t{old_start}\t{old_count}\t{new_start}\t{new_count}\t{status}\t{}\t{}",
+            self.file_name, self.offset, self.fuzz
+        )
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: ", self.file_name, self.header)?;
+        match &self.result {
+            Ok(()) => write!(f, "applied")?,
+            Err(err) if self.conflict => write!(f, "CONFLICT -- {err}")?,
+            Err(err) => write!(f, "FAILED -- {err}")?,
+        }
+        if self.offset != 0 {
+            write!(f, " (offset {} line(s))", self.offset)?;
+        }
+        if self.fuzz != 0 {
+            write!(f, " (fuzz {})", self.fuzz)?;
+        }
+        Ok(())
+    }
+}
+
+// The number of context lines that differ if the hunk lines, without '+'
+// lines, are matched against the text at start, or None if a '-' line
+// differs or the hunk doesn't fit
+fn mismatches<T: AsRef<str>>(
+    expected: &[(char, &str)],
+    text: &[T],
+    start: usize,
+) -> Option<usize> {
+    let text = text.get(start..start + expected.len())?;
+    let mut num = 0usize;
+    for ((kind, line), actual) in expected.iter().zip(text) {
+        if *line != actual.as_ref() {
+            match kind {
+                ' ' => num += 1,
+                _ => return None,
+            }
+        }
+    }
+    Some(num)
+}
+
+// Applies the hunks of a file diff to the lines of the file. Each hunk is
+// looked for at the position in its header, then at increasing offsets,
+// first without fuzz and then with more and more of it. Hunks that can't
+// be found are left out, or written as conflict blocks, and reported as
+// failed.
+pub fn lines<T: AsRef<str>>(
+    file_diff: &FileDiff,
+    text: &[T],
+    options: &Options,
+) -> (Vec<String>, Vec<Report>) {
+    let mut lines: Vec<String> = Vec::new();
+    let mut reports: Vec<Report> = Vec::new();
+    let mut cursor = 0usize; // lines of text before this are done
+    let mut drift = 0i64; // offset of the last hunk that applied
+
+    for hunk in file_diff.hunks() {
+        // the hunk's lines as if going from the '-' to the '+' side
+        let hunk_lines: Vec<(char, &str)> = hunk.lines()[1..]
+            .iter()
+            .filter(|line| !line.starts_with('\\'))
+            .map(|line| match (line.chars().next(), options.reverse) {
+                (Some('-'), true) => ('+', hunk::text(line)),
+                (Some('+'), true) => ('-', hunk::text(line)),
+                (Some(kind), _) => (kind, hunk::text(line)),
+                (None, _) => (' ', ""),
+            })
+            .collect();
+        let expected: Vec<(char, &str)> = hunk_lines
+            .iter()
+            .filter(|(kind, _)| *kind != '+')
+            .copied()
+            .collect();
+
+        let [old_span, new_span] = hunk.header().spans();
+        let first = if options.reverse {
+            new_span[0]
+        } else {
+            old_span[0]
+        };
+        let position = (first - 1 + drift).max(0);
+
+        // the closest start for the least fuzz
+        let found = (0..=options.fuzz).find_map(|fuzz| {
+            (0..=options.max_offset as i64)
+                .flat_map(|offset| [position + offset, position - offset])
+                .filter(|start| *start >= cursor as i64)
+                .find_map(|start| {
+                    let num = mismatches(&expected, text, start as usize)?;
+                    (num <= fuzz).then_some((start as usize, num))
+                })
+        });
+
+        let mut report = Report {
+            file_name: file_diff.header().file_name().clone(),
+            header: hunk.header().clone(),
+            result: Ok(()),
+            offset: 0,
+            fuzz: 0,
+            conflict: false,
+        };
+
+        match found {
+            Some((start, fuzz)) => {
+                lines.extend(
+                    text[cursor..start].iter().map(|s| s.as_ref().to_string()),
+                );
+                let mut index = start;
+                for (kind, line) in hunk_lines.iter() {
+                    match kind {
+                        // the file's own version of fuzzy context
+                        ' ' => lines.push(text[index].as_ref().to_string()),
+                        '-' => {}
+                        _ => lines.push(line.to_string()),
+                    }
+                    if *kind != '+' {
+                        index += 1;
+                    }
+                }
+                cursor = index;
+                drift = start as i64 - (first - 1).max(0);
+                report.offset = drift;
+                report.fuzz = fuzz;
+            }
+            None => {
+                let start = (position as usize).clamp(cursor, text.len());
+                let end = (start + expected.len()).min(text.len());
+                // what is wrong where the hunk should be
+                let detail = match (start..start + expected.len()).find(|i| {
+                    text.get(*i).map(|s| s.as_ref())
+                        != Some(expected[*i - start].1)
+                }) {
+                    Some(i) if i < text.len() => format!(
+                        "expected '{}' at line {}, found '{}'",
+                        expected[i - start].1,
+                        i + 1,
+                        text[i].as_ref()
+                    ),
+                    _ => "the file ends before the hunk".to_string(),
+                };
+                report.result = Err(ApplyErr::new(ApplyErrKind::NotFound {
+                    max_offset: options.max_offset,
+                    line_no: position + 1,
+                    fuzz: options.fuzz,
+                    detail,
+                }));
+                if options.conflicts {
+                    lines.extend(
+                        text[cursor..start]
+                            .iter()
+                            .map(|s| s.as_ref().to_string()),
+                    );
+                    lines.push(format!(
+                        "<<<<<<< {}",
+                        file_diff.header().file_name()
+                    ));
+                    lines.extend(
+                        text[start..end]
+                            .iter()
+                            .map(|s| s.as_ref().to_string()),
+                    );
+                    lines.push("=======".to_string());
+                    lines.extend(
+                        hunk_lines
+                            .iter()
+                            .filter(|(kind, _)| *kind != '-')
+                            .map(|(_, line)| line.to_string()),
+                    );
+                    lines.push(format!(">>>>>>> {}", hunk.header()));
+                    cursor = end;
+                    report.offset = start as i64 - (first - 1).max(0);
+                    report.conflict = true;
+                }
+            }
+        }
+        reports.push(report);
+    }
+
+    lines.extend(text[cursor..].iter().map(|s| s.as_ref().to_string()));
+    (lines, reports)
+}
+
+// Applies a diff to the files under dir. A file is only written if all of
+// its hunks apply, or with Options::conflicts if the failed ones became
+// conflict blocks, and a file that can't be read fails all of them. Files
+// that the diff adds are created, files it deletes removed, and renamed
+// files moved.
+pub fn apply(
+    diff: &Diff,
+    dir: &Path,
+    options: &Options,
+) -> Result<Vec<Report>, ApplyErr> {
+    let mut reports: Vec<Report> = Vec::new();
+    for file_name in diff.file_names() {
+        // a file that could not be fused has diffs to apply one after
+        // another, which are undone backwards
+        let mut file_diffs = diff.get_all(file_name);
+        if options.reverse {
+            file_diffs.reverse();
+        }
+        for file_diff in file_diffs {
+            if !apply_file(file_name, file_diff, dir, options, &mut reports)? {
+                break;
+            }
+        }
+    }
+    Ok(reports)
+}
+
+// Fails every hunk of a file diff with the same error, or the file itself
+// if the diff has no hunks for it
+fn fail_file(
+    file_name: &FilePath,
+    file_diff: &FileDiff,
+    kind: ApplyErrKind,
+    reports: &mut Vec<Report>,
+) {
+    let mut headers: Vec<hunk::Header> = file_diff
+        .hunks()
+        .iter()
+        .map(|hunk| hunk.header().clone())
+        .collect();
+    if headers.is_empty() {
+        headers.push(hunk::Header::default());
+    }
+    reports.extend(headers.into_iter().map(|header| Report {
+        file_name: file_name.clone(),
+        header,
+        result: Err(ApplyErr::new(kind.clone())),
+        offset: 0,
+        fuzz: 0,
+        conflict: false,
+    }));
+}
+
+// Applies the diff of one file, see apply, and tells whether it applied
+fn apply_file(
+    file_name: &FilePath,
+    file_diff: &FileDiff,
+    dir: &Path,
+    options: &Options,
+    reports: &mut Vec<Report>,
+) -> Result<bool, ApplyErr> {
+    let header = file_diff.header();
+    let (source, target) = match options.reverse {
+        false => (&header.old_side().path, &header.new_side().path),
+        true => (&header.new_side().path, &header.old_side().path),
+    };
+
+    let text = match source {
+        Some(path) => {
+            let path = dir.join(path.as_str());
+            debugln!("Reading {}", path.display());
+            match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(err) => {
+                    fail_file(
+                        file_name,
+                        file_diff,
+                        ApplyErrKind::Unreadable {
+                            path: path.clone(),
+                            msg: err.to_string(),
+                        },
+                        reports,
+                    );
+                    return Ok(false);
+                }
+            }
+        }
+        None => {
+            // a file the diff adds must not exist yet
+            if let Some(path) = target {
+                let path = dir.join(path.as_str());
+                if path.exists() {
+                    fail_file(
+                        file_name,
+                        file_diff,
+                        ApplyErrKind::Exists(path),
+                        reports,
+                    );
+                    return Ok(false);
+                }
+            }
+            String::new()
+        }
+    };
+    // the lines keep any '\r' of their endings, and the file its lack of a
+    // final newline, unless the last hunk adds or removes it
+    let final_eol = match file_diff.hunks().last().map(Hunk::missing_eols) {
+        Some(missing) if missing[0] != missing[1] => {
+            !missing[usize::from(!options.reverse)]
+        }
+        _ => text.is_empty() || text.ends_with('\n'),
+    };
+    let text: Vec<&str> = hunk::split_lines(&text).collect();
+
+    let (lines, file_reports) = self::lines(file_diff, &text, options);
+    let failed = file_reports
+        .iter()
+        .any(|report| report.result.is_err() && !report.conflict);
+    let conflicted = file_reports.iter().any(|report| report.conflict);
+    reports.extend(file_reports);
+    if failed || options.dry_run {
+        return Ok(!failed);
+    }
+
+    // a deleted file with conflicts stays for them to be resolved
+    let target = match target {
+        None if conflicted => source,
+        _ => target,
+    };
+
+    if let Some(path) = target {
+        let path = dir.join(path.as_str());
+        debugln!("Writing {}", path.display());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut data = lines.join("\n");
+        if final_eol && !lines.is_empty() {
+            data.push('\n');
+        }
+        fs::write(&path, data)?;
+    }
+    if let Some(path) = source
+        .as_ref()
+        .filter(|path| Some(*path) != target.as_ref())
+    {
+        let path = dir.join(path.as_str());
+        debugln!("Removing {}", path.display());
+        fs::remove_file(path)?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lines, Options};
+    use crate::file_diff::FileDiff;
+
+    fn test(
+        input: &str,
+        reverse: bool,
+        text: &str,
+        expected: Result<&str, ()>,
+    ) {
+        let file_diff =
+            FileDiff::from_lines(&mut input.lines().peekable()).unwrap();
+        let text: Vec<&str> = text.lines().collect();
+        let options = Options {
+            reverse,
+            ..Options::default()
+        };
+        let (lines, reports) = lines(&file_diff, &text, &options);
+        match expected {
+            Ok(expected) => {
+                assert!(reports.iter().all(|report| report.result.is_ok()));
+                assert_eq!(lines, expected.lines().collect::<Vec<&str>>());
+            }
+            Err(()) => {
+                assert!(reports.iter().any(|report| report.result.is_err()))
+            }
+        }
+    }
+
+    fn test_report(
+        options: Options,
+        text: &str,
+        expected: &str,
+        records: &[&str],
+    ) {
+        let file_diff =
+            FileDiff::from_lines(&mut DIFF.lines().peekable()).unwrap();
+        let text: Vec<&str> = text.lines().collect();
+        let (lines, reports) = lines(&file_diff, &text, &options);
+        assert_eq!(lines, expected.lines().collect::<Vec<&str>>());
+        let actual: Vec<String> =
+            reports.iter().map(|report| report.record()).collect();
+        assert_eq!(actual, records);
+    }
+
+    const DIFF: &str = "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,2 +1,3 @@
+ a
++x
+ b
+@@ -4 +4,0 @@
+-d
+";
+
+    #[test]
+    fn case_1() {
+        test(DIFF, false, "a\nb\nc\nd\ne\n", Ok("a\nx\nb\nc\ne\n"));
+    }
+
+    #[test]
+    fn case_2() {
+        test(DIFF, true, "a\nx\nb\nc\ne\n", Ok("a\nb\nc\nd\ne\n"));
+    }
+
+    #[test]
+    fn case_3() {
+        test(DIFF, false, "a\nB\nc\nD\ne\n", Err(()));
+    }
+
+    #[test]
+    fn case_4() {
+        test(DIFF, false, "a\nb\n", Err(()));
+    }
+
+    #[test]
+    fn case_5() {
+        // both hunks 2 lines further down
+        test_report(
+            Options {
+                max_offset: 2,
+                ..Options::default()
+            },
+            "0\n1\na\nb\nc\nd\ne\n",
+            "0\n1\na\nx\nb\nc\ne\n",
+            &[
+                "test.txt\t1\t2\t1\t3\tapplied\t2\t0",
+                "test.txt\t4\t1\t4\t0\tapplied\t2\t0",
+            ],
+        );
+    }
+
+    #[test]
+    fn case_6() {
+        // the file keeps its version of the context
+        test_report(
+            Options {
+                fuzz: 1,
+                ..Options::default()
+            },
+            "a\nB\nc\nd\ne\n",
+            "a\nx\nB\nc\ne\n",
+            &[
+                "test.txt\t1\t2\t1\t3\tapplied\t0\t1",
+                "test.txt\t4\t1\t4\t0\tapplied\t0\t0",
+            ],
+        );
+    }
+
+    #[test]
+    fn case_7() {
+        test_report(
+            Options {
+                conflicts: true,
+                ..Options::default()
+            },
+            "a\nb\nc\nD\ne\n",
+            "a\nx\nb\nc\n<<<<<<< test.txt\nD\n=======\n>>>>>>> @@ -4 +4,0 @@\ne\n",
+            &[
+                "test.txt\t1\t2\t1\t3\tapplied\t0\t0",
+                "test.txt\t4\t1\t4\t0\tconflict\t0\t0",
+            ],
+        );
+    }
+
+    #[test]
+    fn case_8() {
+        // the '-' line never matches with fuzz
+        test_report(
+            Options {
+                fuzz: 2,
+                max_offset: 3,
+                ..Options::default()
+            },
+            "a\nb\nc\nD\ne\n",
+            "a\nx\nb\nc\nD\ne\n",
+            &[
+                "test.txt\t1\t2\t1\t3\tapplied\t0\t0",
+                "test.txt\t4\t1\t4\t0\tfailed\t0\t0",
+            ],
+        );
+    }
+
+    #[test]
+    fn case_9() {
+        // both hunks 2 lines further down, within the default offset
+        test(
+            DIFF,
+            false,
+            "0\n1\na\nb\nc\nd\ne\n",
+            Ok("0\n1\na\nx\nb\nc\ne\n"),
+        );
+    }
+}
@@ -1,23 +1,105 @@
+use std::cell::Cell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::slice::Iter;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::error::{MergeErr, ParseErr};
+use crate::error::{MergeErr, MergeErrKind, ParseErr, ParseErrKind};
 use crate::file_diff;
-use crate::file_diff::FileDiff;
-use crate::macros::{debugln, parse_err};
+use crate::file_diff::{FileDiff, LooseMatch};
+use crate::header;
+use crate::hunk;
+use crate::hunk::Recount;
+use crate::macros::{debugln, merge_err};
+use crate::options::{FuseOptions, OnFailure, ParseOptions};
+use crate::path::FilePath;
+use crate::verify;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Diff {
-    _order: Vec<String>,
-    _map: HashMap<String, FileDiff>,
+    _order: Vec<FilePath>, // in the order first seen in the inputs
+    _map: HashMap<FilePath, FileDiff>,
+    // file diffs to apply after the one in _map, for files that could not
+    // be fused, see OnFailure::Sequential
+    _sequels: HashMap<FilePath, Vec<FileDiff>>,
+    _skipped: Vec<Skipped>,
+    _failed: Vec<Failed>,
+    _sort: Order,
+}
+
+// The order of the files in the output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Order {
+    // the order of the first input, followed by files new in later ones
+    #[default]
+    FirstSeen,
+    Lexicographic,
+    // files in a directory after its subdirectories, like a tree view
+    Tree,
+}
+
+impl Order {
+    fn compare(&self, lhs: &FilePath, rhs: &FilePath) -> Ordering {
+        match self {
+            Order::FirstSeen => Ordering::Equal,
+            Order::Lexicographic => lhs.cmp(rhs),
+            Order::Tree => {
+                let mut lhs_iter = lhs.as_str().split('/').peekable();
+                let mut rhs_iter = rhs.as_str().split('/').peekable();
+                while let (Some(lhs), Some(rhs)) =
+                    (lhs_iter.next(), rhs_iter.next())
+                {
+                    let lhs_is_dir = lhs_iter.peek().is_some();
+                    let rhs_is_dir = rhs_iter.peek().is_some();
+                    if lhs_is_dir != rhs_is_dir {
+                        return rhs_is_dir.cmp(&lhs_is_dir);
+                    }
+                    if lhs != rhs {
+                        return lhs.cmp(rhs);
+                    }
+                }
+                Ordering::Equal
+            }
+        }
+    }
+}
+
+// A region of the input that is not part of any file diff, see
+// ParseOptions::lenient
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skipped {
+    pub line_no: usize, // 1-based
+    pub lines: Vec<String>,
+}
+
+impl std::fmt::Display for Skipped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Skipped {} line(s) starting at line {}: '{}'",
+            self.lines.len(),
+            self.line_no,
+            self.lines.first().map_or("", |s| s.as_str())
+        )
+    }
+}
+
+// A file that could not be fused, see FuseOptions::on_failure
+#[derive(Debug, Clone)]
+pub struct Failed {
+    pub file_name: FilePath,
+    pub err: MergeErr,
+}
+
+impl std::fmt::Display for Failed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file_name, self.err)
+    }
 }
 
 pub struct LineIter<'a> {
-    _diff: &'a Diff,
-    _file_iter: Iter<'a, String>,
+    _file_iter: std::vec::IntoIter<&'a FileDiff>,
     _line_iter: file_diff::LineIter<'a>,
 }
 
@@ -30,91 +112,526 @@ impl<'a> Iterator for LineIter<'a> {
                 return next;
             }
 
-            let next_file = self._file_iter.next()?;
-            let next_diff = self._diff._map.get(next_file);
-            if next_diff.is_none() {
-                panic!("Diff: Entry for '{}' not found", next_file);
-            }
-
-            self._line_iter = next_diff?.line_iter();
+            self._line_iter = self._file_iter.next()?.line_iter();
         }
     }
 }
 
 impl Diff {
     pub fn read(path: &PathBuf) -> Result<Diff, ParseErr> {
+        Self::read_with(path, &ParseOptions::default())
+    }
+
+    pub fn read_with(
+        path: &PathBuf,
+        options: &ParseOptions,
+    ) -> Result<Diff, ParseErr> {
         debugln!("Reading {}", path.display());
-        let data = fs::read_to_string(path)?;
-        Ok(Self::from(data.parse()?))
+        fs::read_to_string(path)
+            .map_err(Into::into)
+            .and_then(|data| {
+                Self::from_lines_with(&mut hunk::split_lines(&data), options)
+            })
+            .map_err(|err| err.with_path(path))
     }
 
     pub fn from_lines<'a, T: Iterator<Item = &'a str>>(
         lines: &mut T,
     ) -> Result<Diff, ParseErr> {
-        let mut peekable = lines.peekable();
-        let mut _order: Vec<String> = Vec::new();
-        let mut _map: HashMap<String, FileDiff> = HashMap::new();
+        Self::from_lines_with(lines, &ParseOptions::default())
+    }
 
-        while peekable.peek().is_some() {
-            let file_diff = FileDiff::from_lines(&mut peekable)?;
-            let file_name = file_diff.header().file_name().to_string();
+    pub fn from_lines_with<'a, T: Iterator<Item = &'a str>>(
+        lines: &mut T,
+        options: &ParseOptions,
+    ) -> Result<Diff, ParseErr> {
+        // the number of lines pulled from the input so far and the last of
+        // them, which is the one returned by the last peek
+        let seen: Cell<(usize, &str)> = Cell::new((0, ""));
+        let mut peekable = lines
+            .inspect(|line| seen.set((seen.get().0 + 1, line)))
+            .peekable();
+        let mut file_diffs: Vec<FileDiff> = Vec::new();
+        let mut line_nos: Vec<usize> = Vec::new();
+        let mut _skipped: Vec<Skipped> = Vec::new();
+
+        // errors number their lines from the start of the file diff, and
+        // an error without a line is about the line the parser stopped at
+        let locate = |err: ParseErr, start: usize| {
+            let (num_read, last) = seen.get();
+            match err.location.line {
+                None if err.location.line_no.is_none() => {
+                    err.at_line(last).at_line_no(num_read)
+                }
+                _ => err.after(start),
+            }
+        };
+
+        while let Some(line) = peekable.peek() {
+            let line_no = seen.get().0;
+            if options.lenient && !header::is_start(line) {
+                let mut skipped = Skipped {
+                    line_no,
+                    lines: Vec::new(),
+                };
+                while let Some(line) =
+                    peekable.next_if(|line| !header::is_start(line))
+                {
+                    skipped.lines.push(line.to_string());
+                }
+                debugln!("{skipped}");
+                _skipped.push(skipped);
+                continue;
+            }
+
+            let file_diff = FileDiff::from_lines_with(&mut peekable, options)
+                .map_err(|err| locate(err, line_no - 1))?;
+            file_diffs.push(file_diff);
+            line_nos.push(line_no);
+        }
+
+        let mut diff = Self::new_at(file_diffs, &line_nos)?;
+        diff._skipped = _skipped;
+        Ok(diff)
+    }
+
+    pub fn new(file_diffs: Vec<FileDiff>) -> Result<Diff, ParseErr> {
+        Self::new_at(file_diffs, &[])
+    }
+
+    // see new, where line_nos are the numbers of the lines that the file
+    // diffs start at, if they were parsed, for errors to point at
+    fn new_at(
+        file_diffs: Vec<FileDiff>,
+        line_nos: &[usize],
+    ) -> Result<Diff, ParseErr> {
+        let mut _order: Vec<FilePath> = Vec::new();
+        let mut _map: HashMap<FilePath, FileDiff> = HashMap::new();
+        for (i, file_diff) in file_diffs.into_iter().enumerate() {
+            let file_name = file_diff.header().file_name().clone();
 
             if _map.contains_key(&file_name) {
-                return Err(parse_err!(
-                    "Diff: Invalid diff - multiple blocks for file {}",
-                    file_name
-                ));
+                let mut err = ParseErr::new(ParseErrKind::MultipleBlocks)
+                    .in_file(&file_name);
+                if let Some(line) = file_diff.header().lines().first() {
+                    err = err.at_line(line);
+                }
+                if let Some(line_no) = line_nos.get(i) {
+                    err = err.at_line_no(*line_no);
+                }
+                return Err(err);
             }
 
             _order.push(file_name.clone());
             _map.insert(file_name, file_diff);
         }
 
-        Ok(Diff { _order, _map })
+        Ok(Diff {
+            _order,
+            _map,
+            _sequels: HashMap::new(),
+            _skipped: Vec::new(),
+            _failed: Vec::new(),
+            _sort: Order::default(),
+        })
+    }
+
+    pub fn file_names(&self) -> Vec<&FilePath> {
+        let mut file_names: Vec<_> = self._order.iter().collect();
+        file_names.sort_by(|lhs, rhs| self._sort.compare(lhs, rhs));
+        file_names
+    }
+
+    // Sets the order of the files for line_iter and the writers, which
+    // also carries over to the results of fuse
+    pub fn sort(&mut self, order: Order) {
+        self._sort = order;
+    }
+
+    pub fn get(&self, file_name: &FilePath) -> Option<&FileDiff> {
+        self._map.get(file_name)
+    }
+
+    // The file diff of the file followed by any to apply after it, see
+    // OnFailure::Sequential
+    pub fn get_all(&self, file_name: &FilePath) -> Vec<&FileDiff> {
+        let sequels = self._sequels.get(file_name).into_iter().flatten();
+        self.get(file_name).into_iter().chain(sequels).collect()
+    }
+
+    // The files that could not be fused, see FuseOptions::on_failure
+    pub fn failed(&self) -> &Vec<Failed> {
+        &self._failed
+    }
+
+    // see FileDiff::loose_matches
+    pub fn loose_matches(&self) -> Vec<(&FilePath, &LooseMatch)> {
+        self._order
+            .iter()
+            .filter_map(|key| Some((key, self._map.get(key)?)))
+            .flat_map(|(key, file_diff)| {
+                file_diff.loose_matches().iter().map(move |m| (key, m))
+            })
+            .collect()
+    }
+
+    // Number of conflict blocks, see FuseOptions::conflicts
+    pub fn num_conflicts(&self) -> usize {
+        self._map.values().map(FileDiff::num_conflicts).sum()
     }
 
-    pub fn line_iter(&self) -> LineIter {
+    pub fn skipped(&self) -> &Vec<Skipped> {
+        &self._skipped
+    }
+
+    fn file_diffs_mut(&mut self) -> impl Iterator<Item = &mut FileDiff> {
+        let sequels = self._sequels.values_mut().flatten();
+        self._map.values_mut().chain(sequels)
+    }
+
+    pub fn trim_context(&mut self, num: usize) {
+        for file_diff in self.file_diffs_mut() {
+            file_diff.trim_context(num);
+        }
+    }
+
+    pub fn rediff(&mut self) {
+        for file_diff in self.file_diffs_mut() {
+            file_diff.rediff();
+        }
+    }
+
+    // Expands the context of every file from the base revision in base_dir,
+    // see FileDiff::expand_context. Added files have no base and are left
+    // as they are.
+    pub fn expand_context(
+        &mut self,
+        base_dir: &Path,
+        num: usize,
+    ) -> Result<(), ParseErr> {
+        for file_name in self._order.iter() {
+            let Some(file_diff) = self._map.get_mut(file_name) else {
+                continue;
+            };
+            let Some(path) = &file_diff.header().old_side().path else {
+                continue;
+            };
+            let path = base_dir.join(path.as_str());
+            debugln!("Reading base {}", path.display());
+            let data = fs::read_to_string(&path).map_err(|err| {
+                ParseErr::new(ParseErrKind::BaseUnreadable {
+                    path: path.clone(),
+                    msg: err.to_string(),
+                })
+                .in_file(file_name)
+            })?;
+            let base: Vec<String> =
+                hunk::split_lines(&data).map(String::from).collect();
+            file_diff.expand_context(&base, num)?;
+        }
+        Ok(())
+    }
+
+    pub fn recount(&mut self) -> Result<Vec<(FilePath, Recount)>, ParseErr> {
+        let mut recounts: Vec<(FilePath, Recount)> = Vec::new();
+        for file_name in self._order.iter() {
+            if let Some(file_diff) = self._map.get_mut(file_name) {
+                for recount in file_diff.recount()? {
+                    recounts.push((file_name.clone(), recount));
+                }
+            }
+        }
+        Ok(recounts)
+    }
+
+    pub fn line_iter(&self) -> LineIter<'_> {
+        let file_diffs: Vec<&FileDiff> = self
+            .file_names()
+            .into_iter()
+            .flat_map(|file_name| self.get_all(file_name))
+            .collect();
         LineIter {
-            _diff: self,
-            _file_iter: self._order.iter(),
+            _file_iter: file_diffs.into_iter(),
             _line_iter: file_diff::LineIter::default(),
         }
     }
 
-    pub fn fuse(mut self, mut other: Diff) -> Result<Diff, MergeErr> {
-        let mut _order: Vec<String> = Vec::new();
-        let mut _map: HashMap<String, FileDiff> = HashMap::new();
-        for (key, val) in other._map.drain() {
-            if let Some(diff) = self._map.remove(&key) {
-                _map.insert(key.clone(), diff.fuse(val)?);
-            } else {
-                _map.insert(key.clone(), val);
+    // The diff that undoes this one
+    pub fn reverse(mut self) -> Diff {
+        let mut _order: Vec<FilePath> = Vec::new();
+        let mut _map: HashMap<FilePath, FileDiff> = HashMap::new();
+        let mut _sequels: HashMap<FilePath, Vec<FileDiff>> = HashMap::new();
+        for key in self._order.drain(..) {
+            let Some(file_diff) = self._map.remove(&key) else {
+                continue;
+            };
+            // file diffs to apply one after another are undone backwards
+            let mut file_diffs: Vec<FileDiff> = std::iter::once(file_diff)
+                .chain(self._sequels.remove(&key).unwrap_or_default())
+                .map(FileDiff::reverse)
+                .collect();
+            file_diffs.reverse();
+            let sequels = file_diffs.split_off(1);
+
+            // a renamed file goes by its old name
+            let Some(file_diff) = file_diffs.pop() else {
+                continue;
+            };
+            let file_name = file_diff.header().file_name().clone();
+            _order.push(file_name.clone());
+            _map.insert(file_name.clone(), file_diff);
+            if !sequels.is_empty() {
+                _sequels.insert(file_name, sequels);
             }
-            _order.push(key);
         }
-        for (key, val) in self._map.drain() {
+        Diff {
+            _order,
+            _map,
+            _sequels,
+            _skipped: Vec::new(),
+            _failed: self._failed,
+            _sort: self._sort,
+        }
+    }
+
+    // The key of the other diff's entry for a file of this diff, which is
+    // the same unless the other diff renames the file
+    fn find_next(&self, other: &Diff, key: &FilePath) -> FilePath {
+        let Some(new_path) = self
+            ._map
+            .get(key)
+            .and_then(|val| val.header().new_side().path.as_ref())
+        else {
+            return key.clone();
+        };
+        other
+            ._order
+            .iter()
+            .find(|other_key| {
+                !self._map.contains_key(other_key)
+                    && other._map.get(other_key).is_some_and(|val| {
+                        val.header().old_side().path.as_ref() == Some(new_path)
+                    })
+            })
+            .unwrap_or(key)
+            .clone()
+    }
+
+    pub fn fuse(self, other: Diff) -> Result<Diff, MergeErr> {
+        self.fuse_with(other, &FuseOptions::default())
+    }
+
+    pub fn fuse_with(
+        mut self,
+        mut other: Diff,
+        options: &FuseOptions,
+    ) -> Result<Diff, MergeErr> {
+        let mut _order: Vec<FilePath> = Vec::new();
+        let mut _map: HashMap<FilePath, FileDiff> = HashMap::new();
+        let mut _sequels: HashMap<FilePath, Vec<FileDiff>> = HashMap::new();
+        let mut _failed: Vec<Failed> = std::mem::take(&mut self._failed);
+        _failed.append(&mut other._failed);
+        for key in self._order.clone().into_iter().chain(other._order.clone())
+        {
+            let other_key = match other._map.contains_key(&key) {
+                true => key.clone(),
+                false => self.find_next(&other, &key),
+            };
+            // later diffs of an omitted file cannot apply either
+            let omitted = _failed.iter().any(|failed| failed.file_name == key);
+            if options.on_failure == OnFailure::Omit && omitted {
+                self._map.remove(&key);
+                other._map.remove(&other_key);
+                continue;
+            }
+
+            let mut sequels = self._sequels.remove(&key).unwrap_or_default();
+            let val = match (
+                self._map.remove(&key),
+                other._map.remove(&other_key),
+            ) {
+                // a file that already failed to fuse stays sequential
+                (Some(lhs), Some(rhs)) if !sequels.is_empty() => {
+                    sequels.push(rhs);
+                    lhs
+                }
+                (Some(lhs), Some(rhs)) => {
+                    let changed = !lhs.is_noop() || !rhs.is_noop();
+                    let inputs = match options.on_failure {
+                        OnFailure::Fail => None,
+                        _ => Some((lhs.clone(), rhs.clone())),
+                    };
+                    match (lhs.fuse_with(rhs, options), inputs) {
+                        (Ok(val), _) if changed && val.is_noop() => {
+                            debugln!(
+                                "Dropping {key}, which is left unchanged"
+                            );
+                            continue;
+                        }
+                        (Ok(val), _) => val,
+                        (Err(err), None) => return Err(err),
+                        (Err(err), Some((lhs, rhs))) => {
+                            debugln!("Could not fuse {key}: {err}");
+                            _failed.push(Failed {
+                                file_name: key.clone(),
+                                err,
+                            });
+                            if options.on_failure == OnFailure::Omit {
+                                continue;
+                            }
+                            sequels.push(rhs);
+                            lhs
+                        }
+                    }
+                }
+                (Some(val), None) | (None, Some(val)) => val,
+                (None, None) => continue,
+            };
+            if let Some(mut other_sequels) = other._sequels.remove(&other_key)
+            {
+                sequels.append(&mut other_sequels);
+            }
+
+            let key = val.header().file_name().clone();
+            if _map.contains_key(&key) {
+                return Err(
+                    MergeErr::new(MergeErrKind::MultipleBlocks).in_file(&key)
+                );
+            }
             _map.insert(key.clone(), val);
+            if !sequels.is_empty() {
+                _sequels.insert(key.clone(), sequels);
+            }
             _order.push(key);
         }
-        _order.sort();
-        Ok(Diff { _order, _map })
+        Ok(Diff {
+            _order,
+            _map,
+            _sequels,
+            _skipped: Vec::new(),
+            _failed,
+            _sort: self._sort,
+        })
+    }
+
+    // What changed between two versions of a patch against the same base,
+    // as a diff from the result of v1 to the result of v2. A file that only
+    // one version changes is undone or changed as in that version, and
+    // lines that both versions change alike are left as context.
+    pub fn interdiff(v1: Diff, v2: Diff) -> Result<Diff, MergeErr> {
+        // the reverse of going back from v2 and on to v1, which keeps the
+        // header lines of v2, unlike fusing v2 onto the reverse of v1
+        let mut diff = v2
+            .reverse()
+            .fuse(v1)
+            .map_err(|err| {
+                err.with_context("The versions disagree on the base")
+            })?
+            .reverse();
+        diff.rediff();
+
+        // files that both versions change alike
+        let noops: Vec<FilePath> = diff
+            ._order
+            .iter()
+            .filter(|key| diff._map.get(*key).is_some_and(FileDiff::is_noop))
+            .cloned()
+            .collect();
+        for key in noops.iter() {
+            debugln!("Dropping {key}, which both versions change alike");
+            diff._map.remove(key);
+        }
+        diff._order.retain(|key| !noops.contains(key));
+        Ok(diff)
+    }
+
+    // Swaps two consecutive diffs into (b', a') with the same combined
+    // effect, so that b' applies to what a applies to, see
+    // FileDiff::commute. Fails if a file's hunks in the two diffs depend on
+    // each other.
+    pub fn commute(
+        mut a: Diff,
+        mut b: Diff,
+    ) -> Result<(Diff, Diff), MergeErr> {
+        let mut pairs: Vec<[FilePath; 2]> = Vec::new();
+        for key in a._order.iter() {
+            let other_key = match b._map.contains_key(key) {
+                true => key.clone(),
+                false => a.find_next(&b, key),
+            };
+            if b._map.contains_key(&other_key) {
+                pairs.push([key.clone(), other_key]);
+            }
+        }
+
+        for [key, other_key] in pairs {
+            let (Some(lhs), Some(rhs)) =
+                (a._map.remove(&key), b._map.remove(&other_key))
+            else {
+                continue;
+            };
+            let (rhs, lhs) = FileDiff::commute(lhs, rhs)?;
+            a._map.insert(key, lhs);
+            b._map.insert(other_key, rhs);
+        }
+        Ok((b, a))
+    }
+
+    // The diff that fuses with the later one into this diff, e.g. a branch
+    // without its last revision
+    pub fn subtract(self, later: &Diff) -> Result<Diff, MergeErr> {
+        let diff = self
+            .clone()
+            .fuse(later.clone().reverse())
+            .map_err(|err| err.with_context("Could not subtract the diff"))?;
+        self.check_separated(diff, later.clone(), 0)
+    }
+
+    // The diff that the earlier one fuses with into this diff, i.e. the
+    // rest of a branch rebased onto the result of its first revision
+    pub fn subtract_first(self, first: &Diff) -> Result<Diff, MergeErr> {
+        // undoing the first diff after undoing this one, which keeps the
+        // header lines of this diff, unlike fusing this onto its reverse
+        let diff = self
+            .clone()
+            .reverse()
+            .fuse(first.clone())
+            .map_err(|err| err.with_context("Could not subtract the diff"))?
+            .reverse();
+        self.check_separated(first.clone(), diff, 1)
+    }
+
+    // Checks that the two parts fuse back into this diff and returns the
+    // one at the given index
+    fn check_separated(
+        &self,
+        first: Diff,
+        second: Diff,
+        index: usize,
+    ) -> Result<Diff, MergeErr> {
+        let mut parts = vec![first, second];
+        if let Some(failure) = verify::verify(&parts, self).first() {
+            return Err(merge_err!(
+                "Could not separate the diffs, they fuse into a different \
+                diff: {failure}"
+            ));
+        }
+        Ok(parts.swap_remove(index))
     }
 }
 
 impl FromStr for Diff {
     type Err = ParseErr;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Diff::from_lines(&mut s.lines())
+        Diff::from_lines(&mut hunk::split_lines(s))
     }
 }
 
-impl ToString for Diff {
-    fn to_string(&self) -> String {
-        let mut string = String::new();
+impl std::fmt::Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for line in self.line_iter() {
-            string += line;
-            string += "\n";
+            writeln!(f, "{line}")?;
         }
-        string
+        Ok(())
     }
 }
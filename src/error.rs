@@ -1,20 +1,166 @@
+use crate::hunk;
+use crate::path::FilePath;
+
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result};
+use std::path::{Path, PathBuf};
+
+// Where in the inputs an error was found, as far as it is known
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    // the input file
+    pub path: Option<PathBuf>,
+    // 1-based number of the offending line in the input
+    pub line_no: Option<usize>,
+    // the offending line itself
+    pub line: Option<String>,
+    pub file_name: Option<FilePath>,
+    // headers of the offending hunks
+    pub headers: Vec<hunk::Header>,
+}
 
-#[derive(Debug)]
+impl Location {
+    fn fmt_prefix(&self, f: &mut Formatter) -> Result {
+        match (&self.path, self.line_no) {
+            (Some(path), Some(line_no)) => {
+                write!(f, "{}:{line_no}: ", path.display())?
+            }
+            (Some(path), None) => write!(f, "{}: ", path.display())?,
+            (None, Some(line_no)) => write!(f, "line {line_no}: ")?,
+            (None, None) => {}
+        }
+        if let Some(file_name) = &self.file_name {
+            write!(f, "{file_name}: ")?;
+        }
+        Ok(())
+    }
+
+    fn fmt_suffix(&self, f: &mut Formatter) -> Result {
+        match self.headers.as_slice() {
+            [] => {}
+            [header] => write!(f, " (hunk {header})")?,
+            headers => {
+                let headers: Vec<_> =
+                    headers.iter().map(|h| h.to_string()).collect();
+                write!(f, " (hunks {})", headers.join(" and "))?
+            }
+        }
+        // the source line under the message, like compilers show it
+        if let Some(line) = &self.line {
+            let line_no = self.line_no.map(|n| n.to_string());
+            write!(f, "\n{:>6} | {line}", line_no.unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrKind {
+    Io(String),
+    // a file or hunk header that can't be parsed, and why
+    BadHeader(String),
+    // the '-' and '+' side line counts of a hunk, which its header disagrees
+    // with
+    CountMismatch { counts: (i64, i64) },
+    // hunks of a file that cover the same lines
+    Overlap,
+    // a file with more than one block in the diff
+    MultipleBlocks,
+    // touching hunks of a file that could not be merged
+    Merge(MergeErrKind),
+    // the base file of a diff, which could not be read, and why
+    BaseUnreadable { path: PathBuf, msg: String },
+    Other(String),
+}
+
+impl Display for ParseErrKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Self::Io(msg) => write!(f, "IOError: {msg}"),
+            Self::BadHeader(msg) => write!(f, "Bad header: {msg}"),
+            Self::CountMismatch { counts } => write!(
+                f,
+                "Found {} '-' side and {} '+' side line(s), which the \
+                header disagrees with",
+                counts.0, counts.1
+            ),
+            Self::Overlap => write!(f, "Hunks overlap"),
+            Self::MultipleBlocks => write!(f, "Multiple blocks for the file"),
+            Self::Merge(kind) => write!(f, "{kind}"),
+            Self::BaseUnreadable { path, msg } => {
+                write!(f, "Could not read base {}: {msg}", path.display())
+            }
+            Self::Other(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ParseErr {
-    _msg: String,
+    pub kind: ParseErrKind,
+    pub location: Box<Location>,
 }
 
 impl ParseErr {
+    pub fn new(kind: ParseErrKind) -> ParseErr {
+        ParseErr {
+            kind,
+            location: Box::default(),
+        }
+    }
+
     pub fn from(msg: String) -> ParseErr {
-        ParseErr { _msg: msg }
+        Self::new(ParseErrKind::Other(msg))
+    }
+
+    // Sets the offending line, unless one is set already
+    pub fn at_line(mut self, line: &str) -> ParseErr {
+        self.location.line.get_or_insert_with(|| line.to_string());
+        self
+    }
+
+    pub fn in_file(mut self, file_name: &FilePath) -> ParseErr {
+        self.location
+            .file_name
+            .get_or_insert_with(|| file_name.clone());
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<hunk::Header>) -> ParseErr {
+        if self.location.headers.is_empty() {
+            self.location.headers = headers;
+        }
+        self
+    }
+
+    pub fn with_path(mut self, path: &Path) -> ParseErr {
+        self.location.path = Some(path.to_path_buf());
+        self
+    }
+
+    // Numbers the offending line, counting from the first line of the
+    // part of the input that is being parsed, unless it has a number
+    pub fn at_line_no(mut self, line_no: usize) -> ParseErr {
+        self.location.line_no.get_or_insert(line_no);
+        self
+    }
+
+    // Renumbers the offending line for the part of the input being parsed
+    // to start after num_lines others
+    pub(crate) fn after(mut self, num_lines: usize) -> ParseErr {
+        if let Some(line_no) = self.location.line_no.as_mut() {
+            *line_no += num_lines;
+        }
+        self
     }
 }
 
 impl Display for ParseErr {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "ParseError: {}", self._msg)
+        write!(f, "ParseError: ")?;
+        self.location.fmt_prefix(f)?;
+        write!(f, "{}", self.kind)?;
+        self.location.fmt_suffix(f)
     }
 }
 
@@ -22,27 +168,182 @@ impl Error for ParseErr {}
 
 impl From<std::io::Error> for ParseErr {
     fn from(err: std::io::Error) -> ParseErr {
-        ParseErr {
-            _msg: format!("IOError: {err:?}"),
+        Self::new(ParseErrKind::Io(format!("{err:?}")))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeErrKind {
+    // lines that the later diff expects to find as the earlier diff leaves
+    // them, but which differ
+    ContextMismatch { left: String, right: String },
+    // hunks that were expected to overlap
+    NoOverlap,
+    // hunks of two diffs that touch the same lines, so that the diffs
+    // can't be reordered
+    Dependent,
+    // a file with more than one block in the result
+    MultipleBlocks,
+    Other(String),
+}
+
+impl Display for MergeErrKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Self::ContextMismatch { left, right } => {
+                write!(f, "Mismatch between lines -- '{left}' and '{right}'")
+            }
+            Self::NoOverlap => {
+                write!(f, "Expected the hunks to overlap, but they do not")
+            }
+            Self::Dependent => write!(f, "The hunks touch the same lines"),
+            Self::MultipleBlocks => write!(f, "Multiple blocks for the file"),
+            Self::Other(msg) => write!(f, "{msg}"),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MergeErr {
-    _msg: String,
+    pub kind: MergeErrKind,
+    pub location: Box<Location>,
+    // what was being done, e.g. 'Could not subtract the diff'
+    pub context: Option<String>,
 }
 
 impl MergeErr {
+    pub fn new(kind: MergeErrKind) -> MergeErr {
+        MergeErr {
+            kind,
+            location: Box::default(),
+            context: None,
+        }
+    }
+
     pub fn from(msg: String) -> MergeErr {
-        MergeErr { _msg: msg }
+        Self::new(MergeErrKind::Other(msg))
+    }
+
+    pub fn in_file(mut self, file_name: &FilePath) -> MergeErr {
+        self.location
+            .file_name
+            .get_or_insert_with(|| file_name.clone());
+        self
+    }
+
+    pub fn with_headers(mut self, headers: Vec<hunk::Header>) -> MergeErr {
+        if self.location.headers.is_empty() {
+            self.location.headers = headers;
+        }
+        self
+    }
+
+    pub fn with_path(mut self, path: &Path) -> MergeErr {
+        self.location.path = Some(path.to_path_buf());
+        self
+    }
+
+    pub fn with_context(mut self, context: &str) -> MergeErr {
+        self.context = Some(context.to_string());
+        self
     }
 }
 
 impl Display for MergeErr {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "MergeError: {}", self._msg)
+        write!(f, "MergeError: ")?;
+        if let Some(context) = &self.context {
+            write!(f, "{context}: ")?;
+        }
+        self.location.fmt_prefix(f)?;
+        write!(f, "{}", self.kind)?;
+        self.location.fmt_suffix(f)
     }
 }
 
 impl Error for MergeErr {}
+
+impl From<MergeErr> for ParseErr {
+    fn from(err: MergeErr) -> ParseErr {
+        ParseErr {
+            kind: ParseErrKind::Merge(err.kind),
+            location: err.location,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApplyErrKind {
+    Io(String),
+    // a file that could not be read, and why
+    Unreadable {
+        path: PathBuf,
+        msg: String,
+    },
+    // a file that the diff adds, which exists already
+    Exists(PathBuf),
+    // a hunk that is not within max_offset lines of line_no, where it was
+    // looked for, with up to fuzz lines of context that differ, and what
+    // is wrong at that line
+    NotFound {
+        max_offset: usize,
+        line_no: i64,
+        fuzz: usize,
+        detail: String,
+    },
+    Other(String),
+}
+
+impl Display for ApplyErrKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Self::Io(msg) => write!(f, "IOError: {msg}"),
+            Self::Unreadable { path, msg } => {
+                write!(f, "Could not read {}: {msg}", path.display())
+            }
+            Self::Exists(path) => {
+                write!(f, "{} already exists", path.display())
+            }
+            Self::NotFound {
+                max_offset,
+                line_no,
+                fuzz,
+                detail,
+            } => write!(
+                f,
+                "Could not find the hunk within {max_offset} line(s) of \
+                line {line_no}, {fuzz} line(s) of fuzz: {detail}"
+            ),
+            Self::Other(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApplyErr {
+    pub kind: ApplyErrKind,
+}
+
+impl ApplyErr {
+    pub fn new(kind: ApplyErrKind) -> ApplyErr {
+        ApplyErr { kind }
+    }
+
+    pub fn from(msg: String) -> ApplyErr {
+        Self::new(ApplyErrKind::Other(msg))
+    }
+}
+
+impl Display for ApplyErr {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "ApplyError: {}", self.kind)
+    }
+}
+
+impl Error for ApplyErr {}
+
+impl From<std::io::Error> for ApplyErr {
+    fn from(err: std::io::Error) -> ApplyErr {
+        Self::new(ApplyErrKind::Io(format!("{err:?}")))
+    }
+}
@@ -1,8 +1,10 @@
-use crate::error::{MergeErr, ParseErr};
+use crate::error::{MergeErr, MergeErrKind, ParseErr, ParseErrKind};
+use crate::fuse::core::CONFLICT_MARKERS;
 use crate::fuse::fuse_iter::fuse_iter;
 use crate::header::Header;
-use crate::hunk::Hunk;
-use crate::macros::{debugln, parse_err, warnln};
+use crate::hunk::{Hunk, Recount};
+use crate::macros::{debugln, merge_err, warnln};
+use crate::options::{FuseOptions, ParseOptions};
 use std::slice::Iter;
 
 use std::iter::Peekable;
@@ -12,22 +14,43 @@ pub struct FileDiff {
     _header: Header,
     _hunks: Vec<Hunk>,
     _num_lines: usize,
+    // see FuseOptions::matching
+    _loose_matches: Vec<LooseMatch>,
 }
 
-pub struct LineIter<'a> {
-    _hunk_iter: Iter<'a, Hunk>,
-    _line_iter: Iter<'a, String>,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
 }
 
-impl LineIter<'_> {
-    pub fn default() -> LineIter<'static> {
-        LineIter {
-            _hunk_iter: Iter::default(),
-            _line_iter: Iter::default(),
+// Lines of the file in between that the diffs only agree on as far as
+// FuseOptions::matching goes, and the side whose text the fused line keeps,
+// if there is a fused line
+#[derive(Debug, Clone, PartialEq)]
+pub struct LooseMatch {
+    pub left: String,
+    pub right: String,
+    pub kept: Option<Side>,
+}
+
+impl std::fmt::Display for LooseMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' matched '{}', ", self.left, self.right)?;
+        match self.kept {
+            Some(Side::Left) => write!(f, "keeping the earlier diff's text"),
+            Some(Side::Right) => write!(f, "keeping the later diff's text"),
+            None => write!(f, "dropping both"),
         }
     }
 }
 
+#[derive(Default)]
+pub struct LineIter<'a> {
+    _hunk_iter: Iter<'a, Hunk>,
+    _line_iter: Iter<'a, String>,
+}
+
 impl<'a> Iterator for LineIter<'a> {
     type Item = &'a String;
     fn next(&mut self) -> Option<Self::Item> {
@@ -43,43 +66,218 @@ impl<'a> Iterator for LineIter<'a> {
 }
 
 impl FileDiff {
+    // A file diff of hunks that are sorted and don't touch
+    pub fn new(_header: Header, _hunks: Vec<Hunk>) -> FileDiff {
+        let mut file_diff = FileDiff {
+            _header,
+            _hunks,
+            _num_lines: 0,
+            _loose_matches: Vec::new(),
+        };
+        file_diff.count_lines();
+        file_diff
+    }
+
+    // Counts the lines again after the hunks changed
+    fn count_lines(&mut self) {
+        self._num_lines = self._header.lines().len()
+            + self
+                ._hunks
+                .iter()
+                .map(|hunk| hunk.lines().len())
+                .sum::<usize>();
+    }
+
+    // Drops the hunks that change nothing, e.g. where the other diff undoes
+    // this one
+    fn drop_unchanged(&mut self) {
+        self._hunks.retain(|hunk| {
+            let [old, new] = hunk.texts();
+            let [old_eol, new_eol] = hunk.missing_eols();
+            let unchanged = old == new && old_eol == new_eol;
+            if unchanged {
+                debugln!("Dropping hunk {hunk} without changes");
+            }
+            !unchanged
+        });
+    }
+
     pub fn from_lines<'a, T: Iterator<Item = &'a str>>(
-        mut lines: &mut Peekable<T>,
+        lines: &mut Peekable<T>,
+    ) -> Result<FileDiff, ParseErr> {
+        Self::from_lines_with(lines, &ParseOptions::default())
+    }
+
+    pub fn from_lines_with<'a, T: Iterator<Item = &'a str>>(
+        lines: &mut Peekable<T>,
+        options: &ParseOptions,
     ) -> Result<FileDiff, ParseErr> {
         let _header = Header::from_lines(lines)?;
-        let mut _num_lines = _header.lines().len();
         let mut _hunks: Vec<Hunk> = Vec::new();
+        // lines read so far, and where each hunk starts, to number errors
+        let mut num_read = _header.lines().len();
+        let mut line_nos: Vec<usize> = Vec::new();
         while let Some(line) = lines.peek() {
             if line.chars().all(char::is_whitespace) {
                 lines.next();
+                num_read += 1;
                 continue;
             } else if !line.starts_with("@@") {
                 break;
             }
-            let hunk = Hunk::from_lines(&mut lines)?;
+            let hunk =
+                Hunk::from_lines_with(lines, options).map_err(|err| {
+                    err.in_file(_header.file_name()).after(num_read)
+                })?;
             debugln!("Parsed hunk {hunk}");
-            _num_lines += hunk.lines().len();
+            line_nos.push(num_read + 1);
+            num_read += hunk.lines().len();
             _hunks.push(hunk);
         }
 
-        for (i, lhs) in _hunks.iter().enumerate() {
-            for rhs in _hunks.iter().skip(i + 1) {
-                if lhs.overlaps(rhs) {
-                    return Err(parse_err!(
-                        "Could not parse file {}: hunks {} and {} overlap",
-                        _header.file_name(),
-                        lhs,
-                        rhs
-                    ));
+        let mut file_diff = FileDiff::new(_header, _hunks);
+
+        // the headers can only be relied on after recount
+        if !options.recount {
+            file_diff.normalise_at(&line_nos)?;
+        }
+
+        Ok(file_diff)
+    }
+
+    // Sorts the hunks and merges the ones that overlap or are adjacent, so
+    // that fuse_iter can rely on the hunks being ordered and disjoint
+    fn normalise(&mut self) -> Result<(), ParseErr> {
+        self.normalise_at(&[])
+    }
+
+    // see normalise, where line_nos are the numbers of the lines that the
+    // hunks start at, if they were parsed, for errors to point at
+    fn normalise_at(&mut self, line_nos: &[usize]) -> Result<(), ParseErr> {
+        let mut sorted: Vec<(Option<usize>, Hunk)> = self
+            ._hunks
+            .drain(..)
+            .enumerate()
+            .map(|(i, hunk)| (line_nos.get(i).copied(), hunk))
+            .collect();
+        sorted.sort_by(|(_, lhs), (_, rhs)| lhs.cmp(rhs));
+
+        let mut hunks: Vec<Hunk> = Vec::new();
+        for (line_no, hunk) in sorted {
+            // the later of the hunks that don't go together
+            let first_line = hunk.lines()[0].clone();
+            let locate = |mut err: ParseErr| {
+                err =
+                    err.in_file(self._header.file_name()).at_line(&first_line);
+                match line_no {
+                    Some(line_no) => err.at_line_no(line_no),
+                    None => err,
+                }
+            };
+            match hunks.pop() {
+                None => hunks.push(hunk),
+                Some(last) if last.touches(&hunk) => {
+                    debugln!("Merging hunks {last} and {hunk}");
+                    let merged = last
+                        .merge(hunk)
+                        .map_err(Into::into)
+                        .map_err(locate)?;
+                    hunks.push(merged);
+                }
+                Some(last) if last.overlaps(&hunk) => {
+                    return Err(locate(ParseErr::new(ParseErrKind::Overlap))
+                        .with_headers(vec![
+                            last.header().clone(),
+                            hunk.header().clone(),
+                        ]));
+                }
+                Some(last) => {
+                    hunks.push(last);
+                    hunks.push(hunk);
                 }
             }
         }
 
-        Ok(FileDiff {
-            _header,
-            _hunks,
-            _num_lines,
-        })
+        self._hunks = hunks;
+        self.count_lines();
+        Ok(())
+    }
+
+    pub fn trim_context(&mut self, num: usize) {
+        let hunks = std::mem::take(&mut self._hunks);
+        for hunk in hunks {
+            self._hunks.extend(hunk.trim_context(num));
+        }
+        self.count_lines();
+    }
+
+    // Re-diffs every hunk, see Hunk::rediff, and drops the ones that turn
+    // out to change nothing. Long runs of context that this leaves can be
+    // split up with trim_context.
+    pub fn rediff(&mut self) {
+        let hunks = std::mem::take(&mut self._hunks);
+        self._hunks = hunks.into_iter().map(Hunk::rediff).collect();
+        self.drop_unchanged();
+        self.count_lines();
+    }
+
+    // Widens the hunks by num lines of context taken from the base file,
+    // joining the ones that come to touch
+    pub fn expand_context(
+        &mut self,
+        base: &[String],
+        num: usize,
+    ) -> Result<(), ParseErr> {
+        let file_name = self._header.file_name();
+        let mut hunks: Vec<Hunk> = Vec::new();
+
+        // the '-' side of each hunk, between those of its neighbours, which
+        // bound the widening so that the hunks only ever share context
+        let mut spans: Vec<[i64; 2]> = vec![[1, 1]];
+        spans.extend(self._hunks.iter().map(|hunk| hunk.header().spans()[0]));
+        spans.push([i64::MAX, i64::MAX]);
+
+        for (i, hunk) in self._hunks.drain(..).enumerate() {
+            let [first, end] = spans[i + 1];
+            let before = num.min((first - spans[i][1]) as usize);
+            let after = num.min((spans[i + 2][0] - end) as usize);
+            let hunk = hunk
+                .expand_context(base, [before, after])
+                .map_err(|err| err.in_file(file_name))?;
+            match hunks.pop() {
+                Some(last) if last.touches(&hunk) => {
+                    let joined = last
+                        .join(hunk)
+                        .map_err(Into::into)
+                        .map_err(|err: ParseErr| err.in_file(file_name))?;
+                    hunks.push(joined);
+                }
+                Some(last) => {
+                    hunks.push(last);
+                    hunks.push(hunk);
+                }
+                None => hunks.push(hunk),
+            }
+        }
+
+        self._hunks = hunks;
+        self.drop_unchanged();
+        self.count_lines();
+        Ok(())
+    }
+
+    pub fn recount(&mut self) -> Result<Vec<Recount>, ParseErr> {
+        let mut recounts: Vec<Recount> = Vec::new();
+        let mut offset = 0i64;
+        for hunk in self._hunks.iter_mut() {
+            if let Some(recount) = hunk.recount(offset) {
+                debugln!("Recounted {recount}");
+                recounts.push(recount);
+            }
+            offset += hunk.offset();
+        }
+        self.normalise()?;
+        Ok(recounts)
     }
 
     pub fn num_lines(&self) -> usize {
@@ -90,7 +288,11 @@ impl FileDiff {
         &self._header
     }
 
-    pub fn line_iter(&self) -> LineIter {
+    pub fn hunks(&self) -> &Vec<Hunk> {
+        &self._hunks
+    }
+
+    pub fn line_iter(&self) -> LineIter<'_> {
         LineIter {
             _hunk_iter: self._hunks.iter(),
             _line_iter: self._header.lines().iter(),
@@ -98,45 +300,222 @@ impl FileDiff {
     }
 
     pub fn fuse(self, other: FileDiff) -> Result<FileDiff, MergeErr> {
+        self.fuse_with(other, &FuseOptions::default())
+    }
+
+    pub fn fuse_with(
+        self,
+        other: FileDiff,
+        options: &FuseOptions,
+    ) -> Result<FileDiff, MergeErr> {
+        // the fusion goes by the lines alone, so the newlines missing at
+        // the ends of the files are taken out of the last hunks and put back
+        // into the fused one, where a diff that doesn't reach the end of the
+        // file leaves it as the other diff says it is in between
+        let [lmissing, rmissing] = [&self, &other].map(FileDiff::missing_eols);
+        let between = lmissing.is_some_and(|missing| missing[1])
+            || rmissing.is_some_and(|missing| missing[0]);
+        let missing = [
+            lmissing.map_or(between, |missing| missing[0]),
+            rmissing.map_or(between, |missing| missing[1]),
+        ];
+        let (mut lhunks, mut rhunks) = (self._hunks, other._hunks);
+        set_missing_eols(&mut lhunks, [false, false]);
+        set_missing_eols(&mut rhunks, [false, false]);
+
         let mut hunks: Vec<Hunk> = Vec::new();
-        let mut _num_lines = self._header.lines().len();
+        let mut _loose_matches = self._loose_matches;
+        _loose_matches.extend(other._loose_matches);
 
-        for item in fuse_iter(self._hunks, other._hunks) {
-            let hunk = item?;
+        let file_name = self._header.file_name().clone();
+        for item in fuse_iter(lhunks, rhunks, options) {
+            let (hunk, mut loose_matches) =
+                item.map_err(|err| err.in_file(&file_name))?;
+            _loose_matches.append(&mut loose_matches);
 
             if hunk.header().is_empty() {
-                if hunk.lines().len() != 0 {
+                if !hunk.lines().is_empty() {
                     warnln!("FileDiff::fuse -- Empty hunk with lines");
                 }
                 continue;
             }
 
-            _num_lines += hunk.lines().len();
             hunks.push(hunk);
         }
+        if lmissing.is_some() || rmissing.is_some() {
+            set_missing_eols(&mut hunks, missing);
+        }
+
+        let mut file_diff = FileDiff {
+            _loose_matches,
+            ..FileDiff::new(self._header.fuse(&other._header), hunks)
+        };
+        file_diff.coalesce()?;
+        Ok(file_diff)
+    }
+
+    // Whether the old and the new file end without a newline, if the last
+    // hunk says so for either of them
+    fn missing_eols(&self) -> Option<[bool; 2]> {
+        let missing = self._hunks.last()?.missing_eols();
+        missing.contains(&true).then_some(missing)
+    }
+
+    // The file diff that undoes this one
+    pub fn reverse(self) -> FileDiff {
+        let hunks = self._hunks.into_iter().map(Hunk::reverse).collect();
+        FileDiff::new(self._header.reverse(), hunks)
+    }
+
+    // Lines that the fused diffs only agree on as far as
+    // FuseOptions::matching goes
+    pub fn loose_matches(&self) -> &Vec<LooseMatch> {
+        &self._loose_matches
+    }
 
-        Ok(FileDiff {
-            _header: self._header.clone(),
-            _hunks: hunks,
-            _num_lines,
-        })
+    // Number of conflict blocks, see FuseOptions::conflicts
+    pub fn num_conflicts(&self) -> usize {
+        self._hunks
+            .iter()
+            .flat_map(|hunk| hunk.lines().iter())
+            .filter(|line| *line == CONFLICT_MARKERS[0])
+            .count()
+    }
+
+    // Whether applying the file diff leaves the file as it is
+    pub fn is_noop(&self) -> bool {
+        let [old, new] = [self._header.old_side(), self._header.new_side()];
+        self._hunks.is_empty()
+            && old.path == new.path
+            && old.mode == new.mode
+            && old.hash == new.hash
+    }
+
+    // Swaps two consecutive file diffs of the same file into ones with the
+    // same combined effect, i.e. (b', a') where b' applies to what a
+    // applies to. The hunks move by the offsets of the other diff's hunks
+    // before them, like in fuse_iter, so hunks of a that touch hunks of b
+    // make the two depend on each other.
+    pub fn commute(
+        a: FileDiff,
+        b: FileDiff,
+    ) -> Result<(FileDiff, FileDiff), MergeErr> {
+        for header in [&a._header, &b._header] {
+            if header.old_side().path != header.new_side().path {
+                return Err(merge_err!(
+                    "Cannot reorder the diffs of {}, which one of them adds, \
+                    deletes or renames",
+                    header.file_name()
+                ));
+            }
+        }
+
+        let mut ahunks: Vec<Hunk> = Vec::new();
+        let mut bhunks: Vec<Hunk> = Vec::new();
+        let mut aiter = a._hunks.into_iter().peekable();
+        let mut biter = b._hunks.into_iter().peekable();
+        let mut aoffset = 0i64;
+        let mut boffset = 0i64;
+        loop {
+            // a's '+' side and b's '-' side are both the file in between
+            let a_first = match (aiter.peek(), biter.peek()) {
+                (None, None) => break,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (Some(ahunk), Some(bhunk)) => {
+                    let [_, [afirst, aend]] = ahunk.header().spans();
+                    let [[bfirst, bend], _] = bhunk.header().spans();
+                    if afirst <= bend && bfirst <= aend {
+                        return Err(MergeErr::new(MergeErrKind::Dependent)
+                            .in_file(a._header.file_name())
+                            .with_headers(vec![
+                                ahunk.header().clone(),
+                                bhunk.header().clone(),
+                            ])
+                            .with_context("Cannot reorder the diffs"));
+                    }
+                    aend < bfirst
+                }
+            };
+
+            if a_first {
+                let Some(hunk) = aiter.next() else { break };
+                aoffset += hunk.offset();
+                ahunks.push(hunk.with_offset(boffset, boffset)?);
+            } else {
+                let Some(hunk) = biter.next() else { break };
+                boffset += hunk.offset();
+                bhunks.push(hunk.with_offset(-aoffset, -aoffset)?);
+            }
+        }
+
+        Ok((
+            FileDiff::new(b._header, bhunks),
+            FileDiff::new(a._header, ahunks),
+        ))
+    }
+
+    // Joins consecutive hunks whose ranges touch or overlap on both sides,
+    // which fusion leaves behind where the inputs' hunks were apart, so
+    // that the result looks like a fresh diff, and drops hunks that change
+    // nothing
+    pub fn coalesce(&mut self) -> Result<(), MergeErr> {
+        let mut hunks: Vec<Hunk> = Vec::new();
+        for hunk in self._hunks.drain(..) {
+            match hunks.pop() {
+                Some(last) if last.touches(&hunk) => {
+                    debugln!("Joining hunks {last} and {hunk}");
+                    hunks.push(last.join(hunk)?);
+                }
+                Some(last) => {
+                    hunks.push(last);
+                    hunks.push(hunk);
+                }
+                None => hunks.push(hunk),
+            }
+        }
+
+        self._hunks = hunks;
+        self.drop_unchanged();
+        self.count_lines();
+        Ok(())
     }
 }
 
-impl ToString for FileDiff {
-    fn to_string(&self) -> String {
-        let mut string = String::new();
+// Marks the sides of the last hunk that miss the newline at their end, see
+// Hunk::with_missing_eols
+fn set_missing_eols(hunks: &mut Vec<Hunk>, missing: [bool; 2]) {
+    if let Some(hunk) = hunks.pop() {
+        hunks.push(hunk.with_missing_eols(missing));
+    }
+}
+
+impl std::fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for line in self.line_iter() {
-            string += line;
-            string += "\n";
+            writeln!(f, "{line}")?;
         }
-        string
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::file_diff::FileDiff;
+    use crate::options::{FuseOptions, ParseOptions};
+
+    // The file diff of text.txt with the hunks
+    fn file_diff(hunks: &str) -> FileDiff {
+        let input = format!(
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+{hunks}"
+        );
+        FileDiff::from_lines(&mut input.lines().peekable()).unwrap()
+    }
 
     fn test(lhs: &str, rhs: &str, expected: &str) {
         let ldiff = match FileDiff::from_lines(&mut lhs.lines().peekable()) {
@@ -163,6 +542,64 @@ mod tests {
         assert_eq!(fused.to_string().as_str(), expected);
     }
 
+    fn test_parse(input: &str, expected: Result<&str, ()>) {
+        let diff = FileDiff::from_lines(&mut input.lines().peekable());
+        match (diff, expected) {
+            (Ok(diff), Ok(expected)) => {
+                assert_eq!(diff.to_string().as_str(), expected)
+            }
+            (Err(_), Err(_)) => {}
+            (actual, _) => panic!("Unexpected result: {actual:?}"),
+        }
+    }
+
+    fn test_recount(input: &str, expected: &str, num_recounts: usize) {
+        let options = ParseOptions {
+            recount: true,
+            ..ParseOptions::default()
+        };
+        let mut lines = input.lines().peekable();
+        let mut diff =
+            FileDiff::from_lines_with(&mut lines, &options).unwrap();
+        assert_eq!(diff.recount().unwrap().len(), num_recounts);
+        assert_eq!(diff.to_string().as_str(), expected);
+    }
+
+    fn test_trim(input: &str, num: usize, expected: &str) {
+        let mut diff =
+            FileDiff::from_lines(&mut input.lines().peekable()).unwrap();
+        diff.trim_context(num);
+        assert_eq!(diff.to_string().as_str(), expected);
+    }
+
+    fn test_commute(a: &str, b: &str, expected: Result<[&str; 2], ()>) {
+        let result = FileDiff::commute(file_diff(a), file_diff(b));
+        match expected {
+            Ok(expected) => {
+                let (b, a) = result.unwrap();
+                assert_eq!(
+                    [b.to_string(), a.to_string()],
+                    expected.map(|hunks| file_diff(hunks).to_string())
+                );
+            }
+            Err(()) => assert!(result.is_err()),
+        }
+    }
+
+    fn test_expand(input: &str, num: usize, expected: Result<&str, ()>) {
+        let base: Vec<String> =
+            "abcdefghij".chars().map(String::from).collect();
+        let mut diff =
+            FileDiff::from_lines(&mut input.lines().peekable()).unwrap();
+        match expected {
+            Ok(expected) => {
+                diff.expand_context(&base, num).unwrap();
+                assert_eq!(diff.to_string().as_str(), expected);
+            }
+            Err(()) => assert!(diff.expand_context(&base, num).is_err()),
+        }
+    }
+
     #[test]
     fn case_1() {
         test(
@@ -416,4 +853,654 @@ Index: text.txt
 ",
         );
     }
+
+    #[test]
+    fn case_7() {
+        test(
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+",
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -4,3 +4,3 @@
+ d
+-e
++E
+ f
+",
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -1,6 +1,6 @@
+ a
+-b
++B
+ c
+ d
+-e
++E
+ f
+",
+        );
+    }
+
+    #[test]
+    fn rediff_case_1() {
+        let mut diff = FileDiff::from_lines(
+            &mut "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -1,2 +1,2 @@
+-a
+-b
++a
++b
+@@ -4 +4 @@
+-d
++D
+"
+            .lines()
+            .peekable(),
+        )
+        .unwrap();
+        diff.rediff();
+        assert_eq!(
+            diff.to_string(),
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -4 +4 @@
+-d
++D
+"
+        );
+    }
+
+    #[test]
+    fn case_8() {
+        test(
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -1 +1,2 @@
+ 1
++x
+@@ -8 +9 @@
+-8
++E
+",
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -2 +2 @@
+-x
++X
+@@ -11 +11 @@
+-10
++T
+",
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+@@ -1 +1,2 @@
+ 1
++X
+@@ -8 +9 @@
+-8
++E
+@@ -10 +11 @@
+-10
++T
+",
+        );
+    }
+
+    #[test]
+    fn recount_case_1() {
+        test_recount(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,3 +1,4 @@
+ a
+-b
++B
+ c
+@@ -10,2 +11,3 @@
+ x
+-y
++Y
++Z
+",
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+@@ -10,2 +10,3 @@
+ x
+-y
++Y
++Z
+",
+            2,
+        );
+    }
+
+    #[test]
+    fn recount_case_2() {
+        test_recount(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,2 +1 @@
+-a
+-b
+@@ -5 +5 @@
+-e
++E
+",
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,2 +0,0 @@
+-a
+-b
+@@ -5 +3 @@
+-e
++E
+",
+            2,
+        );
+    }
+
+    #[test]
+    fn recount_case_3() {
+        let input = "\
+--- x.txt
++++ x.txt
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+--- y.txt
++++ y.txt
+@@ -1 +1 @@
+-c
++C
+";
+        let options = ParseOptions {
+            recount: true,
+            ..ParseOptions::default()
+        };
+        let mut lines = input.lines().peekable();
+        let mut output = String::new();
+        while lines.peek().is_some() {
+            let mut diff =
+                FileDiff::from_lines_with(&mut lines, &options).unwrap();
+            diff.recount().unwrap();
+            output += &diff.to_string();
+        }
+        assert_eq!(
+            output,
+            "\
+--- x.txt
++++ x.txt
+@@ -1,2 +1,2 @@
+ a
+-b
++B
+--- y.txt
++++ y.txt
+@@ -1 +1 @@
+-c
++C
+"
+        );
+    }
+
+    #[test]
+    fn trim_case_1() {
+        test_trim(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,9 +1,9 @@
+ a
+-b
++B
+ c
+ d
+ e
+ f
+ g
+-h
++H
+ i
+",
+            1,
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+@@ -7,3 +7,3 @@
+ g
+-h
++H
+ i
+",
+        );
+    }
+
+    #[test]
+    fn trim_case_2() {
+        test_trim(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,6 +1,5 @@
+ a
+-b
+ c
+ d
+-e
++E
+ f
+@@ -10,2 +9,2 @@
+ j
+ k
+",
+            0,
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -2 +1,0 @@
+-b
+@@ -5 +4 @@
+-e
++E
+",
+        );
+    }
+
+    #[test]
+    fn trim_case_3() {
+        test_trim(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,5 +1,6 @@
+ a
+ b
++c
+ d
+ e
+ f
+",
+            2,
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,4 +1,5 @@
+ a
+ b
++c
+ d
+ e
+",
+        );
+    }
+
+    #[test]
+    fn expand_case_1() {
+        test_expand(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -2 +2 @@
+-b
++B
+@@ -9 +8,0 @@
+-i
+",
+            2,
+            Ok("\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,4 +1,4 @@
+ a
+-b
++B
+ c
+ d
+@@ -7,4 +7,3 @@
+ g
+ h
+-i
+ j
+"),
+        );
+    }
+
+    #[test]
+    fn expand_case_2() {
+        test_expand(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -2 +2 @@
+-b
++B
+@@ -5,0 +6 @@
++x
+@@ -7 +8 @@
+ g
+",
+            usize::MAX,
+            Ok("\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,10 +1,11 @@
+ a
+-b
++B
+ c
+ d
+ e
++x
+ f
+ g
+ h
+ i
+ j
+"),
+        );
+    }
+
+    #[test]
+    fn expand_case_3() {
+        test_expand(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -2,2 +1,0 @@
+-b
+-x
+",
+            0,
+            Err(()),
+        );
+    }
+
+    #[test]
+    fn expand_case_4() {
+        test_expand(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -10,2 +10 @@
+ j
+-k
+",
+            3,
+            Err(()),
+        );
+    }
+
+    #[test]
+    fn parse_case_1() {
+        test_parse(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -5 +5 @@
+-e
++E
+@@ -1 +1 @@
+-a
++A
+",
+            Ok("\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1 +1 @@
+-a
++A
+@@ -5 +5 @@
+-e
++E
+"),
+        );
+    }
+
+    #[test]
+    fn parse_case_2() {
+        test_parse(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -3,4 +3,4 @@
+ 3
+ 4
+-5
++Y
+ 6
+@@ -1,4 +1,4 @@
+ 1
+-2
++X
+ 3
+ 4
+",
+            Ok("\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,6 +1,6 @@
+ 1
+-2
++X
+ 3
+ 4
+-5
++Y
+ 6
+"),
+        );
+    }
+
+    #[test]
+    fn parse_case_3() {
+        test_parse(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,3 +1,4 @@
+ 1
++N
+ 2
+ 3
+@@ -4 +5 @@
+-4
++Y
+",
+            Ok("\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,4 +1,5 @@
+ 1
++N
+ 2
+ 3
+-4
++Y
+"),
+        );
+    }
+
+    #[test]
+    fn parse_case_4() {
+        test_parse(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,3 +1,3 @@
+ 1
+-2
++X
+ 3
+@@ -2,2 +2,2 @@
+ 2
+ 3
+",
+            Err(()),
+        );
+    }
+
+    #[test]
+    fn parse_case_5() {
+        test_parse(
+            "\
+Index: test.txt
+===================================================================
+--- test.txt
++++ test.txt
+@@ -1,2 +1 @@
+ 1
+-2
+@@ -2 +2 @@
+-2
++Z
+",
+            Err(()),
+        );
+    }
+
+    #[test]
+    fn commute_case_1() {
+        test_commute(
+            "@@ -1 +1,2 @@\n 1\n+x\n",
+            "@@ -8 +8 @@\n-7\n+S\n",
+            Ok(["@@ -7 +7 @@\n-7\n+S\n", "@@ -1 +1,2 @@\n 1\n+x\n"]),
+        );
+    }
+
+    #[test]
+    fn commute_case_2() {
+        test_commute(
+            "@@ -8 +7,0 @@\n-8\n@@ -12 +11 @@\n-12\n+C\n",
+            "@@ -1 +1,2 @@\n 1\n+x\n@@ -9 +10 @@\n-10\n+N\n",
+            Ok([
+                "@@ -1 +1,2 @@\n 1\n+x\n@@ -10 +11 @@\n-10\n+N\n",
+                "@@ -9 +8,0 @@\n-8\n@@ -13 +12 @@\n-12\n+C\n",
+            ]),
+        );
+    }
+
+    #[test]
+    fn commute_case_3() {
+        test_commute(
+            "@@ -2,2 +2,2 @@\n 2\n-3\n+T\n",
+            "@@ -3 +3 @@\n-T\n+U\n",
+            Err(()),
+        );
+    }
+
+    #[test]
+    fn conflicts_case_1() {
+        let left = file_diff("@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+        let right = file_diff("@@ -2,2 +2,2 @@\n-x\n+X\n c\n");
+        assert!(left.clone().fuse(right.clone()).is_err());
+
+        let options = FuseOptions {
+            conflicts: true,
+            ..FuseOptions::default()
+        };
+        let fused = left.fuse_with(right, &options).unwrap();
+        assert_eq!(fused.num_conflicts(), 1);
+        assert_eq!(
+            fused.to_string(),
+            file_diff(
+                "@@ -1,3 +1,8 @@\n a\n-b\n+<<<<<<< after the earlier diff\n\
+                +B\n+=======\n+x\n+>>>>>>> expected by the later diff\n+X\n \
+                c\n"
+            )
+            .to_string()
+        );
+    }
 }
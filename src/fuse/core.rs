@@ -2,22 +2,55 @@ use crate::fuse::info_source::InfoSource;
 use crate::fuse::line::Line;
 use crate::fuse::line_counter::LineCounter;
 
-use crate::error::MergeErr;
+use crate::error::{MergeErr, MergeErrKind};
+use crate::file_diff::{LooseMatch, Side};
 use crate::hunk::{Header, Hunk};
 use crate::macros::merge_err;
+use crate::options::{FuseOptions, Matching};
 
 use core::cmp::Ordering;
 
+type RankedLine = ((i64, i64), Line);
+
+// The lines around a conflict block, between the text after the earlier
+// diff and the text the later diff expects
+pub const CONFLICT_MARKERS: [&str; 3] = [
+    "+<<<<<<< after the earlier diff",
+    "+=======",
+    "+>>>>>>> expected by the later diff",
+];
+
 pub fn fuse<T: InfoSource>(
-    mut header: Header,
+    header: Header,
     source: T,
 ) -> Result<Hunk, MergeErr> {
+    fuse_with(header, source, &FuseOptions::default()).map(|(hunk, _)| hunk)
+}
+
+// Fuses the hunks of the source, see fuse, along with the lines matched
+// loosely on the way
+pub fn fuse_with<T: InfoSource>(
+    mut header: Header,
+    source: T,
+    options: &FuseOptions,
+) -> Result<(Hunk, Vec<LooseMatch>), MergeErr> {
     let mut counter = LineCounter::default();
-    let mut data: Vec<((i64, i64), Line)> = Vec::new();
-    let mut drain = Drain::<T> { source };
+    let mut data: Vec<RankedLine> = Vec::new();
+    let mut drain = Drain::<T> {
+        source,
+        conflicts: options.conflicts,
+        matching: options.matching,
+        loose_matches: Vec::new(),
+    };
 
+    // the lines of consecutive conflicts, which go into one block
+    let mut conflict: [Vec<String>; 2] = [Vec::new(), Vec::new()];
     while let Some(item) = drain.next() {
-        match item? {
+        let item = item?;
+        if !matches!(item, FuseItem::Conflict(..)) {
+            flush_conflict(&mut conflict, &mut data, &mut counter)?;
+        }
+        match item {
             FuseItem::Single(info) => {
                 data.push((counter.update(&info)?, info));
             }
@@ -25,28 +58,68 @@ pub fn fuse<T: InfoSource>(
                 data.push((counter.update(&linfo)?, linfo));
                 data.push((counter.update(&rinfo)?, rinfo));
             }
+            FuseItem::Conflict(linfo, rinfo) => {
+                // a base line that the earlier diff kept is replaced by the
+                // conflict block
+                if linfo.prefix() == ' ' {
+                    let info: Line =
+                        (format!("-{}", linfo.text()), linfo.rank).into();
+                    data.push((counter.update(&info)?, info));
+                }
+                conflict[0].push(format!("+{}", linfo.text()));
+                conflict[1].push(format!("+{}", rinfo.text()));
+            }
             FuseItem::None => {}
         }
     }
+    flush_conflict(&mut conflict, &mut data, &mut counter)?;
 
-    counter.update_header(&mut header);
+    counter.update_header(&mut header)?;
 
-    Ok(Hunk::new(
+    let hunk = Hunk::new(
         header,
         sort(data)?.into_iter().map(|(_, info)| info.line).collect(),
-    ))
+    );
+    Ok((hunk, drain.loose_matches))
+}
+
+// Adds the conflict block of the pending conflicts, if any
+fn flush_conflict(
+    conflict: &mut [Vec<String>; 2],
+    data: &mut Vec<RankedLine>,
+    counter: &mut LineCounter,
+) -> Result<(), MergeErr> {
+    if conflict[0].is_empty() {
+        return Ok(());
+    }
+    let [left, right] = std::mem::take(conflict);
+    let [start, middle, end] = CONFLICT_MARKERS.map(String::from);
+    let lines = std::iter::once(start)
+        .chain(left)
+        .chain(std::iter::once(middle))
+        .chain(right)
+        .chain(std::iter::once(end));
+    for line in lines {
+        let info: Line = (line, 0).into();
+        data.push((counter.update(&info)?, info));
+    }
+    Ok(())
 }
 
 enum FuseItem {
     None,
     Single(Line),
     Pair(Line, Line),
+    Conflict(Line, Line), // lines the diffs disagree on
 }
 
 type DrainItem = Option<Result<FuseItem, MergeErr>>;
 
 struct Drain<T: InfoSource> {
     source: T,
+    conflicts: bool,    // see FuseOptions::conflicts
+    matching: Matching, // see FuseOptions::matching
+    loose_matches: Vec<LooseMatch>,
 }
 
 impl<T: InfoSource> Drain<T> {
@@ -77,8 +150,9 @@ impl<T: InfoSource> Drain<T> {
             ['-', '+'] => {
                 let left = self.source.next_left()?;
                 let mut right = self.source.next_right()?;
-                if left.line[1..] == right.line[1..] {
-                    right.line.replace_range(0..1, " ");
+                // compared exactly, as a loose match would drop the change
+                if left.text() == right.text() {
+                    right.line = format!(" {}", right.text());
                     Some(Ok(FuseItem::Single(right)))
                 } else {
                     Some(Ok(FuseItem::Pair(left, right)))
@@ -112,51 +186,100 @@ impl<T: InfoSource> Drain<T> {
     fn skip(&mut self) -> DrainItem {
         let left = self.source.next_left()?;
         let right = self.source.next_right()?;
-        if left.line[1..] == right.line[1..] {
+        if self.matches(&left, &right, None) {
             Some(Ok(FuseItem::None))
+        } else if self.conflicts {
+            Some(Ok(FuseItem::Conflict(left, right)))
         } else {
-            Some(Err(merge_err!(
-                "skip: Mismatch between lines -- '{left:?}' and '{right:?}'"
-            )))
+            Some(Err(mismatch(left, right)))
         }
     }
 
+    // The added line reads as the later diff has it, which is what the
+    // result has
     fn skip_take_left(&mut self) -> DrainItem {
         let left = self.source.next_left()?;
         let right = self.source.next_right()?;
-        if left.line[1..] == right.line[1..] {
-            Self::take(Some(left))
+        if self.matches(&left, &right, Some(Side::Right)) {
+            Self::take(Some(with_text(left, &right)))
+        } else if self.conflicts {
+            Some(Ok(FuseItem::Conflict(left, right)))
         } else {
-            Some(Err(merge_err!(
-                "skip_take: Mismatch between lines -- '{:?}' and '{:?}'",
-                left,
-                right
-            )))
+            Some(Err(mismatch(left, right)))
         }
     }
 
+    // The line reads as the earlier diff has it, which is what the base has
     fn skip_take_right(&mut self) -> DrainItem {
         let left = self.source.next_left()?;
         let right = self.source.next_right()?;
-        if left.line[1..] == right.line[1..] {
-            Self::take(Some(right))
+        if self.matches(&left, &right, Some(Side::Left)) {
+            Self::take(Some(with_text(right, &left)))
+        } else if self.conflicts {
+            Some(Ok(FuseItem::Conflict(left, right)))
         } else {
-            Some(Err(merge_err!(
-                "skip_take: Mismatch between lines -- '{:?}' and '{:?}'",
-                left,
-                right
-            )))
+            Some(Err(mismatch(left, right)))
         }
     }
 
     fn take(info: Option<Line>) -> DrainItem {
         Some(Ok(FuseItem::Single(info?)))
     }
+
+    // Whether the lines match, see FuseOptions::matching. Lines that differ
+    // but match are recorded along with the side whose text is kept.
+    fn matches(
+        &mut self,
+        left: &Line,
+        right: &Line,
+        kept: Option<Side>,
+    ) -> bool {
+        if left.text() == right.text() {
+            return true;
+        }
+        if !texts_match(self.matching, left.text(), right.text()) {
+            return false;
+        }
+        self.loose_matches.push(LooseMatch {
+            left: left.line.clone(),
+            right: right.line.clone(),
+            kept,
+        });
+        true
+    }
+}
+
+fn texts_match(matching: Matching, left: &str, right: &str) -> bool {
+    let no_space = |text: &str| {
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+    };
+    match matching {
+        Matching::Exact => left == right,
+        Matching::IgnoreEol => {
+            left.strip_suffix('\r').unwrap_or(left)
+                == right.strip_suffix('\r').unwrap_or(right)
+        }
+        Matching::IgnoreTrailingSpace => left.trim_end() == right.trim_end(),
+        Matching::IgnoreAllSpace => no_space(left) == no_space(right),
+    }
+}
+
+// The line with the text of the other line
+fn with_text(mut info: Line, other: &Line) -> Line {
+    info.line = format!("{}{}", info.prefix(), other.text());
+    info
+}
+
+fn mismatch(left: Line, right: Line) -> MergeErr {
+    MergeErr::new(MergeErrKind::ContextMismatch {
+        left: left.line,
+        right: right.line,
+    })
 }
 
-fn sort(
-    mut data: Vec<((i64, i64), Line)>,
-) -> Result<Vec<((i64, i64), Line)>, MergeErr> {
+fn sort(mut data: Vec<RankedLine>) -> Result<Vec<RankedLine>, MergeErr> {
     let mut err: Option<MergeErr> = None;
     let mut update_err = |e: MergeErr| {
         if err.is_none() {
@@ -177,14 +300,14 @@ fn sort(
                 return lhs_group.cmp(rhs_group);
             }
 
-            let lhs_prefix = if let Some(val) = linfo.line.chars().nth(0) {
+            let lhs_prefix = if let Some(val) = linfo.line.chars().next() {
                 val
             } else {
                 update_err(merge_err!("Empty line in sort"));
                 ' '
             };
 
-            let rhs_prefix = if let Some(val) = rinfo.line.chars().nth(0) {
+            let rhs_prefix = if let Some(val) = rinfo.line.chars().next() {
                 val
             } else {
                 update_err(merge_err!("Empty line in sort"));
@@ -1,17 +1,21 @@
-use crate::fuse::core::fuse;
+use crate::fuse::core::fuse_with;
 use crate::fuse::info_chain::{HunkIter, InfoChain};
 
 use crate::error::MergeErr;
+use crate::file_diff::LooseMatch;
 use crate::hunk::Hunk;
 use crate::macros::{debugln, merge_err};
+use crate::options::FuseOptions;
 
-use core::cmp::Ordering;
 use std::iter::Peekable;
 
+// The hunks of the fused file diff, each with the lines matched loosely
+// in fusing it, see fuse::core::fuse_with
 pub fn fuse_iter(
     lhunks: Vec<Hunk>,
     rhunks: Vec<Hunk>,
-) -> impl Iterator<Item = Result<Hunk, MergeErr>> {
+    options: &FuseOptions,
+) -> impl Iterator<Item = Result<(Hunk, Vec<LooseMatch>), MergeErr>> {
     // A file diff is an ordered set X[i], i >= 0 of non-overlapping hunks.
     // Consider two file diffs, X and Y.
     //
@@ -33,11 +37,24 @@ pub fn fuse_iter(
         rhunks.len()
     );
 
+    // offsets of the last n hunks of either side at index n, so that the
+    // offsets of the hunks fused together can be told by the hunks left
+    let suffix_offsets = |hunks: &Vec<Hunk>| -> Vec<i64> {
+        let mut offsets = vec![0i64];
+        for hunk in hunks.iter().rev() {
+            offsets.push(offsets[offsets.len() - 1] + hunk.offset());
+        }
+        offsets
+    };
+    let loffsets = suffix_offsets(&lhunks);
+    let roffsets = suffix_offsets(&rhunks);
+
+    let options = options.clone();
     let mut liter = lhunks.into_iter().peekable();
     let mut riter = rhunks.into_iter().peekable();
     let mut loffset = 0i64;
     let mut roffset = 0i64;
-    std::iter::from_fn(move || -> Option<Result<Hunk, MergeErr>> {
+    std::iter::from_fn(move || -> Option<Result<(Hunk, _), MergeErr>> {
         match [liter.peek(), riter.peek()] {
             [None, None] => None,
             [None, Some(rhs)] => {
@@ -46,7 +63,7 @@ pub fn fuse_iter(
                     (loffset, roffset)
                 );
                 roffset += rhs.offset();
-                Some(riter.next()?.with_offset(loffset, 0))
+                Some(unfused(riter.next()?.with_offset(-loffset, 0)))
             }
             [Some(lhs), None] => {
                 debugln!(
@@ -54,35 +71,38 @@ pub fn fuse_iter(
                     (loffset, roffset)
                 );
                 loffset += lhs.offset();
-                Some(liter.next()?.with_offset(0, roffset))
+                Some(unfused(liter.next()?.with_offset(0, roffset)))
             }
             [Some(lhs), Some(rhs)] => {
                 if !lhs.header().should_fuse(rhs.header()) {
-                    if lhs.cmp(rhs) == Ordering::Less {
+                    if comes_first(lhs, rhs) {
                         debugln!(
                             "fuse_iter: left -- {lhs} -- {:?}",
                             (loffset, roffset)
                         );
                         loffset += lhs.offset();
-                        Some(liter.next()?.with_offset(0, roffset))
+                        Some(unfused(liter.next()?.with_offset(0, roffset)))
                     } else {
                         debugln!(
                             "fuse_iter: right -- {rhs} -- {:?}",
                             (loffset, roffset)
                         );
                         roffset += rhs.offset();
-                        Some(riter.next()?.with_offset(loffset, 0))
+                        Some(unfused(riter.next()?.with_offset(-loffset, 0)))
                     }
                 } else {
                     debugln!(
                         "fuse_iter: Merging {lhs} and {rhs} -- {:?}",
                         (loffset, roffset)
                     );
-                    match fuse_overlapping(&mut liter, &mut riter) {
-                        Ok(hunk) => {
-                            loffset += hunk.offset();
-                            roffset += hunk.offset();
-                            Some(Ok(hunk))
+                    let lengths = (liter.len(), riter.len());
+                    match fuse_overlapping(&mut liter, &mut riter, &options) {
+                        Ok(fused) => {
+                            loffset +=
+                                loffsets[lengths.0] - loffsets[liter.len()];
+                            roffset +=
+                                roffsets[lengths.1] - roffsets[riter.len()];
+                            Some(Ok(fused))
                         }
                         err => Some(err),
                     }
@@ -92,14 +112,33 @@ pub fn fuse_iter(
     })
 }
 
+// Whether a hunk of the earlier diff comes before a hunk of the later one
+// that it doesn't overlap, going by the file in between, i.e. the '+' side
+// of the one and the '-' side of the other
+fn comes_first(lhs: &Hunk, rhs: &Hunk) -> bool {
+    let [_, [_, lhs_end]] = lhs.header().spans();
+    let [[rhs_first, _], _] = rhs.header().spans();
+    lhs_end <= rhs_first
+}
+
+// A hunk of either diff alone, which has no lines to match
+fn unfused(
+    hunk: Result<Hunk, MergeErr>,
+) -> Result<(Hunk, Vec<LooseMatch>), MergeErr> {
+    hunk.map(|hunk| (hunk, Vec::new()))
+}
+
 fn fuse_overlapping(
     lhunks: &mut Peekable<HunkIter>,
     rhunks: &mut Peekable<HunkIter>,
-) -> Result<Hunk, MergeErr> {
-    let header = if let (Some(l), Some(r)) = (lhunks.peek(), rhunks.peek()) {
-        l.header().fuse(r.header())
-    } else {
-        return Err(merge_err!("fuse_overlapping: peek returned None"));
+    options: &FuseOptions,
+) -> Result<(Hunk, Vec<LooseMatch>), MergeErr> {
+    let headers = match (lhunks.peek(), rhunks.peek()) {
+        (Some(l), Some(r)) => vec![l.header().clone(), r.header().clone()],
+        _ => return Err(merge_err!("fuse_overlapping: peek returned None")),
     };
-    fuse(header, InfoChain::new(lhunks, rhunks)?)
+    let header = headers[0].fuse(&headers[1]);
+    InfoChain::new(lhunks, rhunks)
+        .and_then(|source| fuse_with(header, source, options))
+        .map_err(|err| err.with_headers(headers))
 }
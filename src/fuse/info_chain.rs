@@ -62,7 +62,7 @@ impl<'a, const T: char> Chain<'_, T> {
                     }
                 }
 
-                let (header, lines) = self.hunk_iter.next().unwrap().unpack();
+                let (header, lines) = self.hunk_iter.next()?.unpack();
                 debugln!("Chain<{T}>: new hunk {header}");
 
                 self.header = header;
@@ -110,7 +110,7 @@ impl<'a> InfoChain<'_> {
     }
 }
 
-impl<'a> InfoSource for InfoChain<'_> {
+impl InfoSource for InfoChain<'_> {
     fn peek(&mut self) -> [Option<&Line>; 2] {
         let lhdr = self.lchain.header();
         let rhdr = self.rchain.header();
@@ -14,7 +14,7 @@ impl InfoIter {
         let kind = '+';
         InfoIter {
             lines,
-            rank: header.fields[2],
+            rank: header.spans()[1][0],
             kind,
         }
     }
@@ -23,7 +23,7 @@ impl InfoIter {
         let kind = '-';
         InfoIter {
             lines,
-            rank: header.fields[0],
+            rank: header.spans()[0][0],
             kind,
         }
     }
@@ -34,7 +34,7 @@ impl Iterator for InfoIter {
 
     fn next(&mut self) -> Option<Line> {
         let line = self.lines.next()?;
-        let info: Line = (line, self.rank.clone()).into();
+        let info: Line = (line, self.rank).into();
         if info.line.starts_with([self.kind, ' ']) {
             self.rank += 1;
         }
@@ -54,8 +54,8 @@ impl Default for InfoIter {
 
 #[cfg(test)]
 mod tests {
-    use crate::fuse::line::Line;
     use crate::fuse::info_iter::{InfoIter, LineIter};
+    use crate::fuse::line::Line;
 
     fn split(line: &str) -> LineIter {
         line.char_indices()
@@ -66,7 +66,7 @@ mod tests {
     }
 
     fn test(actual: InfoIter, expected: Vec<(&str, i64)>) {
-        for (act, exp) in actual.zip(expected.into_iter()) {
+        for (act, exp) in actual.zip(expected) {
             assert_eq!(act, Line::from(exp));
         }
     }
@@ -90,7 +90,7 @@ mod tests {
     #[test]
     fn case_1() {
         test_left(
-            [1, 0, 1, 0],
+            [1, 6, 1, 6],
             split("+ -+ +- -"),
             vec![
                 ("+", 1),
@@ -109,7 +109,7 @@ mod tests {
     #[test]
     fn case_2() {
         test_right(
-            [3, 0, 3, 0],
+            [3, 2, 3, 4],
             split("+++- "),
             vec![("+", 3), ("+", 3), ("+", 3), ("-", 3), (" ", 4)],
         );
@@ -1,3 +1,5 @@
+use crate::hunk;
+
 #[derive(Debug, PartialEq)]
 pub struct Line {
     pub line: String,
@@ -6,7 +8,11 @@ pub struct Line {
 
 impl Line {
     pub fn prefix(&self) -> char {
-        self.line.chars().nth(0).unwrap_or('!')
+        self.line.chars().next().unwrap_or('!')
+    }
+
+    pub fn text(&self) -> &str {
+        hunk::text(&self.line)
     }
 }
 
@@ -3,6 +3,7 @@ use crate::fuse::line::Line;
 use crate::hunk::Header;
 use crate::macros::merge_err;
 
+#[derive(Default)]
 pub struct LineCounter {
     num_added: i64,
     num_removed: i64,
@@ -11,18 +12,6 @@ pub struct LineCounter {
     total_unchanged: i64,
 }
 
-impl Default for LineCounter {
-    fn default() -> LineCounter {
-        LineCounter {
-            num_added: 0,
-            num_removed: 0,
-            total_added: 0,
-            total_removed: 0,
-            total_unchanged: 0,
-        }
-    }
-}
-
 impl LineCounter {
     pub fn update(&mut self, info: &Line) -> Result<(i64, i64), MergeErr> {
         match info.prefix() {
@@ -49,7 +38,7 @@ impl LineCounter {
         }
     }
 
-    pub fn update_header(&self, header: &mut Header) {
+    pub fn update_header(&self, header: &mut Header) -> Result<(), MergeErr> {
         header.fields[1] = self.total_removed + self.total_unchanged;
         header.fields[3] = self.total_added + self.total_unchanged;
 
@@ -58,13 +47,26 @@ impl LineCounter {
                 header.fields[0] = 0;
                 header.fields[2] = 0;
             }
+            // an empty side starts at the line before it, see Header::fuse
             [_, 0] => {
-                header.fields[2] = header.fields[0] - 1;
+                header.fields[2] -= 1;
             }
             [0, _] => {
-                header.fields[0] = header.fields[2] - 1;
+                header.fields[0] -= 1;
             }
             _ => {}
         }
+
+        // hunks whose line numbers disagree, e.g. where one takes lines
+        // that the other added as lines of the base
+        let [old_start, old_count, new_start, new_count] = header.fields;
+        if old_start < i64::from(old_count > 0)
+            || new_start < i64::from(new_count > 0)
+        {
+            return Err(merge_err!(
+                "The fused hunk {header} starts before the first line"
+            ));
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diff::Diff;
+use crate::error::ParseErr;
+use crate::file_diff::FileDiff;
+use crate::header::Header;
+use crate::hunk::{self, Hunk};
+use crate::macros::{debugln, parse_err};
+use crate::path::FilePath;
+
+// How to write the generated diffs, which are in svn format
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub context: usize, // lines of context around the changes
+    pub old_label: String,
+    pub new_label: String,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            context: 3,
+            old_label: "(revision 1)".to_string(),
+            new_label: "(working copy)".to_string(),
+        }
+    }
+}
+
+// The hunks that turn the old lines into the new ones
+pub fn hunks<T: AsRef<str>>(
+    old: &[T],
+    new: &[T],
+    context: usize,
+) -> Vec<Hunk> {
+    hunks_with(old, new, [false, false], context)
+}
+
+// see hunks, where missing_eols says which of the old and the new lines
+// end without a newline
+pub fn hunks_with<T: AsRef<str>>(
+    old: &[T],
+    new: &[T],
+    missing_eols: [bool; 2],
+    context: usize,
+) -> Vec<Hunk> {
+    let old: Vec<&str> = old.iter().map(|line| line.as_ref()).collect();
+    let new: Vec<&str> = new.iter().map(|line| line.as_ref()).collect();
+    if old.is_empty() && new.is_empty() {
+        return Vec::new();
+    }
+
+    // a single hunk over the whole file, cut down to the changes
+    let (num_old, num_new) = (old.len() as i64, new.len() as i64);
+    let header: hunk::Header = [
+        if num_old == 0 { 0 } else { 1 },
+        num_old,
+        if num_new == 0 { 0 } else { 1 },
+        num_new,
+    ]
+    .into();
+    Hunk::from_texts(header, &old, &new)
+        .with_missing_eols(missing_eols)
+        .trim_context(context)
+}
+
+// The diff of one file between two texts, where None stands for a file
+// that doesn't exist. Identical texts give no diff, while texts that
+// differ only in their final newline do.
+pub fn file_diff(
+    file_name: &FilePath,
+    old: Option<&str>,
+    new: Option<&str>,
+    options: &Options,
+) -> Result<Option<FileDiff>, ParseErr> {
+    let old_lines: Vec<&str> =
+        old.map_or(Vec::new(), |s| hunk::split_lines(s).collect());
+    let new_lines: Vec<&str> =
+        new.map_or(Vec::new(), |s| hunk::split_lines(s).collect());
+    let missing_eol = |text: Option<&str>| {
+        text.is_some_and(|s| !s.is_empty() && !s.ends_with('\n'))
+    };
+    let hunks = hunks_with(
+        &old_lines,
+        &new_lines,
+        [missing_eol(old), missing_eol(new)],
+        options.context,
+    );
+    if hunks.is_empty() && old.is_some() == new.is_some() {
+        return Ok(None);
+    }
+
+    let label = |text: Option<&str>, label: &str| match text {
+        Some(_) => label.to_string(),
+        None => "(nonexistent)".to_string(),
+    };
+    let lines: Vec<String> = vec![
+        format!("Index: {file_name}"),
+        "=".repeat(67),
+        format!("--- {file_name}\t{}", label(old, &options.old_label)),
+        format!("+++ {file_name}\t{}", label(new, &options.new_label)),
+    ];
+    let header =
+        Header::from_lines(&mut lines.iter().map(|s| s.as_str()).peekable())?;
+    Ok(Some(FileDiff::new(header, hunks)))
+}
+
+// The diff between two files, named after the new one
+pub fn files(
+    old: &Path,
+    new: &Path,
+    options: &Options,
+) -> Result<Diff, ParseErr> {
+    let file_name = FilePath::new(&new.to_string_lossy());
+    let old = fs::read_to_string(old)?;
+    let new = fs::read_to_string(new)?;
+    let file_diffs = file_diff(&file_name, Some(&old), Some(&new), options)?;
+    Diff::new(file_diffs.into_iter().collect())
+}
+
+// The diff between two directory trees, with paths relative to them.
+// Files only in the new tree are added and files only in the old one
+// deleted.
+pub fn dirs(
+    old: &Path,
+    new: &Path,
+    options: &Options,
+) -> Result<Diff, ParseErr> {
+    let mut paths: BTreeSet<PathBuf> = BTreeSet::new();
+    list(old, Path::new(""), &mut paths)?;
+    list(new, Path::new(""), &mut paths)?;
+
+    let read = |dir: &Path, path: &Path| -> Result<Option<String>, ParseErr> {
+        let path = dir.join(path);
+        if path.is_file() {
+            Ok(Some(fs::read_to_string(path)?))
+        } else {
+            Ok(None)
+        }
+    };
+
+    let mut file_diffs: Vec<FileDiff> = Vec::new();
+    for path in paths {
+        debugln!("Comparing {}", path.display());
+        let file_name = FilePath::new(&path.to_string_lossy());
+        let old_text = read(old, &path)?;
+        let new_text = read(new, &path)?;
+        if let Some(file_diff) = file_diff(
+            &file_name,
+            old_text.as_deref(),
+            new_text.as_deref(),
+            options,
+        )? {
+            file_diffs.push(file_diff);
+        }
+    }
+    Diff::new(file_diffs)
+}
+
+// Collects the paths of the files under dir/prefix relative to dir
+fn list(
+    dir: &Path,
+    prefix: &Path,
+    paths: &mut BTreeSet<PathBuf>,
+) -> Result<(), ParseErr> {
+    let path = dir.join(prefix);
+    if !path.is_dir() {
+        return Err(parse_err!(
+            "Generate: '{}' is not a directory",
+            path.display()
+        ));
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let relative = prefix.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            list(dir, &relative, paths)?;
+        } else {
+            paths.insert(relative);
+        }
+    }
+    Ok(())
+}
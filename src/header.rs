@@ -1,51 +1,469 @@
-use crate::error::ParseErr;
-use crate::macros::parse_err;
+use crate::error::{ParseErr, ParseErrKind};
+use crate::path::{quote, unquote, FilePath};
+
+use std::iter::Peekable;
 
 #[derive(Debug, Clone)]
 pub struct Header {
     _lines: Vec<String>,
-    _file_name: String,
+    _file_name: FilePath,
+    _old: Side,
+    _new: Side,
+}
+
+// What the header says about one side of the diff
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Side {
+    pub path: Option<FilePath>, // None if the file doesn't exist
+    pub mode: Option<String>,
+    pub hash: Option<String>, // abbreviated git blob hash
+    pub label: String,        // e.g. '(revision 2)' or a timestamp
+}
+
+// Extended header lines that may follow 'diff --git'
+const GIT_EXTENDED: [&str; 13] = [
+    "old mode ",
+    "new mode ",
+    "deleted file mode ",
+    "new file mode ",
+    "copy from ",
+    "copy to ",
+    "rename from ",
+    "rename to ",
+    "similarity index ",
+    "dissimilarity index ",
+    "index ",
+    "Binary files ",
+    "GIT binary patch",
+];
+
+pub fn is_start(line: &str) -> bool {
+    line.starts_with("Index: ")
+        || line.starts_with("diff --git ")
+        || line.starts_with("--- ")
+}
+
+fn bad_header(line: &str, msg: &str) -> ParseErr {
+    ParseErr::new(ParseErrKind::BadHeader(msg.to_string())).at_line(line)
+}
+
+// Marks an error from parsing a part of the header line as a bad header
+fn in_header(err: ParseErr, line: &str) -> ParseErr {
+    let err = match err.kind {
+        ParseErrKind::Other(msg) => ParseErr {
+            kind: ParseErrKind::BadHeader(msg),
+            ..err
+        },
+        _ => err,
+    };
+    err.at_line(line)
+}
+
+// Numbers the offending line by its index in the header lines
+fn at(index: usize) -> impl Fn(ParseErr) -> ParseErr {
+    move |err| err.at_line_no(index + 1)
+}
+
+fn end_of_input() -> ParseErr {
+    ParseErr::new(ParseErrKind::BadHeader(
+        "The input ends in the header".to_string(),
+    ))
 }
 
 fn get_line<'a, T: Iterator<Item = &'a str>>(
     lines: &mut T,
 ) -> Result<String, ParseErr> {
+    // a diff with CRLF line endings throughout has them in the header too
     lines
         .next()
-        .map(|s| s.to_string())
-        .ok_or(parse_err!("Header: Could not get line"))
+        .map(|s| s.trim_end_matches('\r').to_string())
+        .ok_or_else(end_of_input)
+}
+
+fn get_field<'a>(line: &'a str, prefix: &str) -> Result<&'a str, ParseErr> {
+    line.strip_prefix(prefix)
+        .ok_or_else(|| bad_header(line, &format!("Expected '{prefix}'")))
 }
 
-fn get_file_name(line: &String) -> Result<String, ParseErr> {
-    line.strip_prefix("Index: ")
-        .map(|s| s.to_string())
-        .ok_or(parse_err!("Header: Unexpected suffix in '{line}'"))
+fn get_path(line: &str, prefix: &str) -> Result<Option<FilePath>, ParseErr> {
+    let (path, _) = FilePath::parse(get_field(line, prefix)?)
+        .map_err(|err| in_header(err, line))?;
+    Ok(path)
+}
+
+fn get_side(line: &str, prefix: &str) -> Result<Side, ParseErr> {
+    let (path, label) = FilePath::parse(get_field(line, prefix)?)
+        .map_err(|err| in_header(err, line))?;
+    Ok(Side {
+        // svn marks added and deleted files this way
+        path: path.filter(|_| label != "(nonexistent)"),
+        label: label.to_string(),
+        ..Side::default()
+    })
+}
+
+// Splits the paths in 'diff --git a/x b/x', which are only quoted if they
+// contain special characters, so unquoted paths with spaces are split where
+// the two halves name the same file
+fn get_git_paths(line: &str) -> Result<[FilePath; 2], ParseErr> {
+    let field = get_field(line, "diff --git ")?;
+    if field.starts_with('"') {
+        let (old, rest) =
+            unquote(field).map_err(|err| in_header(err, line))?;
+        let (new, _) = FilePath::parse(rest.trim_start())
+            .map_err(|err| in_header(err, line))?;
+        let new = new.ok_or_else(|| bad_header(line, "Missing path"))?;
+        return Ok([FilePath::new(&old), new]);
+    }
+
+    if let Some(index) = field.find(" \"") {
+        let (new, _) = unquote(&field[index + 1..])
+            .map_err(|err| in_header(err, line))?;
+        return Ok([FilePath::new(&field[..index]), FilePath::new(&new)]);
+    }
+
+    let splits: Vec<_> = field.match_indices(' ').map(|(i, _)| i).collect();
+    let split = splits
+        .iter()
+        .find(|i| {
+            let old = FilePath::new(&field[..**i]);
+            let new = FilePath::new(&field[**i + 1..]);
+            old.strip_first() == new.strip_first()
+        })
+        .or(splits.first())
+        .ok_or_else(|| bad_header(line, "Missing path"))?;
+
+    Ok([
+        FilePath::new(&field[..*split]),
+        FilePath::new(&field[*split + 1..]),
+    ])
+}
+
+// The a/ and b/ prefixes of the paths in 'diff --git a/x b/x', which are
+// absent in, e.g., 'git diff --no-prefix'
+fn get_git_prefixes(paths: &[FilePath; 2]) -> [&'static str; 2] {
+    match [paths[0].strip_prefix("a/"), paths[1].strip_prefix("b/")] {
+        [Some(_), Some(_)] => ["a/", "b/"],
+        _ => ["", ""],
+    }
+}
+
+// Moves a path in a header line from one prefix to the other, e.g. from
+// 'b/x' to 'a/x', also when the path is quoted
+fn swap_prefix(field: &str, from: &str, to: &str) -> String {
+    if let Some(rest) = field.strip_prefix(from) {
+        return format!("{to}{rest}");
+    }
+    match field.strip_prefix('"').and_then(|f| f.strip_prefix(from)) {
+        Some(rest) => format!("\"{to}{rest}"),
+        None => field.to_string(),
+    }
 }
 
 impl Header {
     pub fn from_lines<'a, T: Iterator<Item = &'a str>>(
-        lines: &mut T,
+        lines: &mut Peekable<T>,
+    ) -> Result<Header, ParseErr> {
+        match lines.peek() {
+            Some(line) if line.starts_with("Index: ") => {
+                Self::from_svn_lines(lines)
+            }
+            Some(line) if line.starts_with("diff --git ") => {
+                Self::from_git_lines(lines)
+            }
+            Some(line) if line.starts_with("--- ") => {
+                Self::from_unified_lines(lines)
+            }
+            Some(line) => Err(bad_header(
+                line,
+                "Expected 'Index: ', 'diff --git ' or '--- '",
+            )
+            .at_line_no(1)),
+            None => Err(end_of_input()),
+        }
+    }
+
+    fn new(
+        _lines: Vec<String>,
+        _old: Side,
+        _new: Side,
+    ) -> Result<Header, ParseErr> {
+        let _file_name =
+            _new.path.clone().or(_old.path.clone()).ok_or_else(|| {
+                bad_header(&_lines[0], "No path").at_line_no(1)
+            })?;
+
+        Ok(Header {
+            _lines,
+            _file_name,
+            _old,
+            _new,
+        })
+    }
+
+    // Index: path
+    // ===================================================================
+    // --- path    (revision 1)
+    // +++ path    (working copy)
+    fn from_svn_lines<'a, T: Iterator<Item = &'a str>>(
+        lines: &mut Peekable<T>,
     ) -> Result<Header, ParseErr> {
-        let _lines: Vec<_> = vec![
-            get_line(lines)?,
-            get_line(lines)?,
-            get_line(lines)?,
-            get_line(lines)?,
-        ];
+        let mut _lines: Vec<_> = vec![get_line(lines)?, get_line(lines)?];
+        let path =
+            FilePath::new(get_field(&_lines[0], "Index: ").map_err(at(0))?);
+        if !_lines[1].starts_with("===") {
+            return Err(
+                bad_header(&_lines[1], "Expected an underline").at_line_no(2)
+            );
+        }
 
-        let _file_name = _lines
-            .get(0)
-            .map(get_file_name)
-            .ok_or(parse_err!("Header: Missing first line"))??;
+        let mut old = Side {
+            path: Some(path.clone()),
+            ..Side::default()
+        };
+        let mut new = old.clone();
+        if lines.peek().is_some_and(|line| line.starts_with("--- ")) {
+            _lines.push(get_line(lines)?);
+            _lines.push(get_line(lines)?);
+            old = get_side(&_lines[2], "--- ").map_err(at(2))?;
+            new = get_side(&_lines[3], "+++ ").map_err(at(3))?;
+        }
 
-        Ok(Header { _lines, _file_name })
+        Self::new(_lines, old, new)
+    }
+
+    // diff --git a/path b/path
+    // <extended header lines>
+    // --- a/path
+    // +++ b/path
+    fn from_git_lines<'a, T: Iterator<Item = &'a str>>(
+        lines: &mut Peekable<T>,
+    ) -> Result<Header, ParseErr> {
+        let mut _lines: Vec<_> = vec![get_line(lines)?];
+        let [old_path, new_path] = get_git_paths(&_lines[0]).map_err(at(0))?;
+
+        let [old_prefix, new_prefix] =
+            get_git_prefixes(&[old_path.clone(), new_path.clone()]);
+        let mut old = Side {
+            path: old_path.strip_prefix(old_prefix),
+            ..Side::default()
+        };
+        let mut new = Side {
+            path: new_path.strip_prefix(new_prefix),
+            ..Side::default()
+        };
+
+        while let Some(line) = lines.peek() {
+            if !GIT_EXTENDED.iter().any(|prefix| line.starts_with(prefix)) {
+                break;
+            }
+            let line = get_line(lines)?;
+            if line.starts_with("rename from ") {
+                old.path = get_path(&line, "rename from ")
+                    .map_err(at(_lines.len()))?;
+            } else if line.starts_with("rename to ") {
+                new.path =
+                    get_path(&line, "rename to ").map_err(at(_lines.len()))?;
+            } else if let Some(mode) = line.strip_prefix("new file mode ") {
+                old.path = None;
+                new.mode = Some(mode.to_string());
+            } else if let Some(mode) = line.strip_prefix("deleted file mode ")
+            {
+                new.path = None;
+                old.mode = Some(mode.to_string());
+            } else if let Some(mode) = line.strip_prefix("old mode ") {
+                old.mode = Some(mode.to_string());
+            } else if let Some(mode) = line.strip_prefix("new mode ") {
+                new.mode = Some(mode.to_string());
+            } else if let Some(field) = line.strip_prefix("index ") {
+                // index <old>..<new>[ <mode>]
+                let (hashes, mode) =
+                    field.split_once(' ').unwrap_or((field, ""));
+                if let Some((old_hash, new_hash)) = hashes.split_once("..") {
+                    old.hash = Some(old_hash.to_string());
+                    new.hash = Some(new_hash.to_string());
+                }
+                if !mode.is_empty() {
+                    old.mode = Some(mode.to_string());
+                    new.mode = Some(mode.to_string());
+                }
+            }
+            _lines.push(line);
+        }
+
+        if lines.peek().is_some_and(|line| line.starts_with("--- ")) {
+            _lines.push(get_line(lines)?);
+            _lines.push(get_line(lines)?);
+            let num_lines = _lines.len();
+            let old_side = get_side(&_lines[num_lines - 2], "--- ")
+                .map_err(at(num_lines - 2))?;
+            let new_side = get_side(&_lines[num_lines - 1], "+++ ")
+                .map_err(at(num_lines - 1))?;
+            old.path = old_side
+                .path
+                .map(|path| path.strip_prefix(old_prefix).unwrap_or(path));
+            new.path = new_side
+                .path
+                .map(|path| path.strip_prefix(new_prefix).unwrap_or(path));
+            old.label = old_side.label;
+            new.label = new_side.label;
+        }
+
+        Self::new(_lines, old, new)
+    }
+
+    // --- path    <date>
+    // +++ path    <date>
+    fn from_unified_lines<'a, T: Iterator<Item = &'a str>>(
+        lines: &mut Peekable<T>,
+    ) -> Result<Header, ParseErr> {
+        let _lines: Vec<_> = vec![get_line(lines)?, get_line(lines)?];
+        let mut old = get_side(&_lines[0], "--- ").map_err(at(0))?;
+        let mut new = get_side(&_lines[1], "+++ ").map_err(at(1))?;
+
+        // 'diff -ru old/dir new/dir' names the same file under different
+        // top level directories, and 'diff -u file.orig file' under
+        // different names, both meaning to patch a single file, while
+        // equal paths are taken as they are
+        match (&old.path, &new.path) {
+            (Some(old_path), Some(new_path)) if old_path != new_path => {
+                let stripped =
+                    [old_path.strip_first(), new_path.strip_first()];
+                new.path = match stripped {
+                    [Some(old_path), Some(new_path)]
+                        if old_path == new_path =>
+                    {
+                        Some(new_path)
+                    }
+                    _ => Some(new_path.clone()),
+                };
+                old.path = new.path.clone();
+            }
+            // an added or deleted file, e.g. '+++ b/x' after '--- /dev/null',
+            // which later diffs name without the a/ or b/ prefix
+            (None, Some(path)) => {
+                new.path =
+                    Some(path.strip_prefix("b/").unwrap_or(path.clone()))
+            }
+            (Some(path), None) => {
+                old.path =
+                    Some(path.strip_prefix("a/").unwrap_or(path.clone()))
+            }
+            _ => {}
+        }
+
+        Self::new(_lines, old, new)
+    }
+
+    // The header of the fusion of this file diff with a later one, which
+    // keeps the lines of this header
+    pub fn fuse(&self, other: &Header) -> Header {
+        Header {
+            _lines: self._lines.clone(),
+            // the later name of a file renamed by the other diff
+            _file_name: other
+                ._new
+                .path
+                .clone()
+                .or(self._old.path.clone())
+                .unwrap_or(self._file_name.clone()),
+            _old: self._old.clone(),
+            _new: other._new.clone(),
+        }
+    }
+
+    // The header of the file diff that undoes this one, with the sides and
+    // every line that names one of them swapped, so that an added file
+    // becomes a deleted one and a rename goes the other way
+    pub fn reverse(&self) -> Header {
+        let mut _lines = self._lines.clone();
+        let mut prefixes = ["", ""];
+        if let Some(Ok(paths)) = _lines
+            .first()
+            .filter(|line| line.starts_with("diff --git "))
+            .map(|line| get_git_paths(line))
+        {
+            prefixes = get_git_prefixes(&paths);
+            let [old, new] = paths.map(|path| quote(path.as_str()));
+            _lines[0] = format!(
+                "diff --git {} {}",
+                swap_prefix(&new, prefixes[1], prefixes[0]),
+                swap_prefix(&old, prefixes[0], prefixes[1])
+            );
+        }
+
+        for line in _lines.iter_mut() {
+            if let Some(mode) = line.strip_prefix("new file mode ") {
+                *line = format!("deleted file mode {mode}");
+            } else if let Some(mode) = line.strip_prefix("deleted file mode ")
+            {
+                *line = format!("new file mode {mode}");
+            } else if let Some(field) = line.strip_prefix("index ") {
+                let (hashes, mode) =
+                    field.split_once(' ').map_or((field, ""), |(h, m)| (h, m));
+                if let Some((old_hash, new_hash)) = hashes.split_once("..") {
+                    *line = match mode {
+                        "" => format!("index {new_hash}..{old_hash}"),
+                        _ => format!("index {new_hash}..{old_hash} {mode}"),
+                    };
+                }
+            }
+        }
+
+        // lines that come in pairs, one for each side
+        for [old_prefix, new_prefix] in [
+            ["old mode ", "new mode "],
+            ["rename from ", "rename to "],
+            ["copy from ", "copy to "],
+            ["--- ", "+++ "],
+        ] {
+            let find = |prefix: &str| {
+                _lines.iter().position(|line| line.starts_with(prefix))
+            };
+            let (Some(old_index), Some(new_index)) =
+                (find(old_prefix), find(new_prefix))
+            else {
+                continue;
+            };
+            let old = _lines[old_index][old_prefix.len()..].to_string();
+            let new = _lines[new_index][new_prefix.len()..].to_string();
+            _lines[old_index] = format!(
+                "{old_prefix}{}",
+                swap_prefix(&new, prefixes[1], prefixes[0])
+            );
+            _lines[new_index] = format!(
+                "{new_prefix}{}",
+                swap_prefix(&old, prefixes[0], prefixes[1])
+            );
+        }
+
+        let _old = self._new.clone();
+        let _new = self._old.clone();
+        Header {
+            _lines,
+            _file_name: _new
+                .path
+                .clone()
+                .or(_old.path.clone())
+                .unwrap_or(self._file_name.clone()),
+            _old,
+            _new,
+        }
     }
 
     pub fn lines(&self) -> &Vec<String> {
         &self._lines
     }
 
-    pub fn file_name(&self) -> &str {
+    pub fn file_name(&self) -> &FilePath {
         &self._file_name
     }
+
+    pub fn old_side(&self) -> &Side {
+        &self._old
+    }
+
+    pub fn new_side(&self) -> &Side {
+        &self._new
+    }
 }
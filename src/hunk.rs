@@ -3,44 +3,146 @@ mod info_source;
 
 pub use header::Header;
 
-use crate::error::{MergeErr, ParseErr};
+use crate::error::{MergeErr, MergeErrKind, ParseErr, ParseErrKind};
 use crate::fuse::core::fuse;
+use crate::header::is_start;
 use crate::macros::{merge_err, parse_err};
+use crate::myers::{self, Edit};
+use crate::options::ParseOptions;
 
 use core::cmp::Ordering;
 use std::iter::Peekable;
 
+// The lines of a text, which unlike str::lines keep the '\r' of CRLF line
+// endings, so that a line differs from one without it
+pub fn split_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive('\n')
+        .map(|line| line.strip_suffix('\n').unwrap_or(line))
+}
+
+// The line that diff puts after the last line of a side of a hunk when
+// the file doesn't end with a newline
+pub const NO_EOL: &str = "\\ No newline at end of file";
+
+// The text of a hunk line, without its '-', '+' or ' ' prefix
+pub fn text(line: &str) -> &str {
+    let mut chars = line.chars();
+    chars.next();
+    chars.as_str()
+}
+
 #[derive(Clone, Debug)]
 pub struct Hunk {
     _lines: Vec<String>,
     _header: Header,
 }
 
+// A header adjustment made by Hunk::recount
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recount {
+    pub before: Header,
+    pub after: Header,
+}
+
+impl std::fmt::Display for Recount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {}", self.before, self.after)
+    }
+}
+
 impl Hunk {
     pub fn new(_header: Header, mut _lines: Vec<String>) -> Hunk {
         _lines.insert(0, _header.to_string());
         Hunk { _header, _lines }
     }
 
+    // The hunk with the header that turns the old lines into the new ones,
+    // as found by myers::diff
+    pub fn from_texts(_header: Header, old: &[&str], new: &[&str]) -> Hunk {
+        let (mut old_iter, mut new_iter) = (old.iter(), new.iter());
+        let lines: Vec<String> = myers::diff(old, new)
+            .into_iter()
+            .map(|edit| match edit {
+                Edit::Keep => {
+                    old_iter.next();
+                    format!(" {}", new_iter.next().unwrap_or(&""))
+                }
+                Edit::Remove => format!("-{}", old_iter.next().unwrap_or(&"")),
+                Edit::Add => format!("+{}", new_iter.next().unwrap_or(&"")),
+            })
+            .collect();
+        Hunk::new(_header, lines)
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn cmp(&self, other: &Hunk) -> Ordering {
         self._header.cmp(&other._header)
     }
 
     pub fn from_lines<'a, T: Iterator<Item = &'a str>>(
         lines: &mut Peekable<T>,
+    ) -> Result<Hunk, ParseErr> {
+        Self::from_lines_with(lines, &ParseOptions::default())
+    }
+
+    pub fn from_lines_with<'a, T: Iterator<Item = &'a str>>(
+        lines: &mut Peekable<T>,
+        options: &ParseOptions,
     ) -> Result<Hunk, ParseErr> {
         if let Some(line) = lines.peek() {
             if !line.starts_with("@@") {
-                return Err(parse_err!("Expected hunk header, got '{line}'"));
+                return Err(ParseErr::new(ParseErrKind::BadHeader(
+                    "Expected a hunk header".to_string(),
+                ))
+                .at_line(line)
+                .at_line_no(1));
             }
 
-            let _header = Header::parse(line)?;
-            let mut _lines: Vec<String> = vec![line.to_string()];
+            let _header =
+                Header::parse(line).map_err(|err| err.at_line_no(1))?;
+            let mut _lines: Vec<String> =
+                vec![line.trim_end_matches('\r').to_string()];
             lines.next();
 
+            // stop once the header counts are reached, so that any text
+            // following the hunk is left for the caller, unless the counts
+            // are to be recomputed anyway, but after the '\' line that
+            // may follow the last line
             let mut counts: (i64, i64) = (0, 0);
-            while let Some(line) = lines.peek() {
-                match line.chars().nth(0).unwrap_or('!') {
+            while options.recount
+                || counts.0 < _header.fields[1]
+                || counts.1 < _header.fields[3]
+                || lines.peek().is_some_and(|line| line.starts_with('\\'))
+            {
+                let Some(line) = lines.peek() else {
+                    break;
+                };
+                // without the counts, the hunk ends at the next hunk or
+                // file diff
+                if options.recount
+                    && (line.starts_with("@@") || is_start(line))
+                {
+                    break;
+                }
+                // editors and mail clients tend to strip the leading space
+                // of empty context lines, so while the header says there's
+                // context left, treat an empty line as context, or always
+                // when lenient, if the header is to be recounted
+                let context_left = if options.recount {
+                    options.lenient
+                } else {
+                    counts.0 < _header.fields[1]
+                        && counts.1 < _header.fields[3]
+                };
+                if matches!(*line, "" | "\r") && context_left {
+                    counts.0 += 1;
+                    counts.1 += 1;
+                    _lines.push(format!(" {line}"));
+                    lines.next();
+                    continue;
+                }
+
+                match line.chars().next().unwrap_or('!') {
                     '-' => {
                         counts.0 += 1;
                     }
@@ -51,6 +153,7 @@ impl Hunk {
                         counts.0 += 1;
                         counts.1 += 1;
                     }
+                    '\\' if _lines.len() > 1 => {}
                     _ => {
                         break;
                     }
@@ -59,12 +162,16 @@ impl Hunk {
                 lines.next();
             }
 
-            if counts.0 != _header.fields[1] || counts.1 != _header.fields[3] {
-                return Err(parse_err!(
-                    "Hunk validation failed: line count = {:?}, header = {:?}",
+            if !options.recount
+                && (counts.0 != _header.fields[1]
+                    || counts.1 != _header.fields[3])
+            {
+                return Err(ParseErr::new(ParseErrKind::CountMismatch {
                     counts,
-                    _header
-                ));
+                })
+                .at_line(&_lines[0])
+                .at_line_no(1)
+                .with_headers(vec![_header]));
             }
 
             Ok(Hunk { _lines, _header })
@@ -91,6 +198,10 @@ impl Hunk {
         self._header.overlaps(&other._header)
     }
 
+    pub fn touches(&self, other: &Hunk) -> bool {
+        self._header.touches(&other._header)
+    }
+
     pub fn with_offset(self, left: i64, right: i64) -> Result<Hunk, MergeErr> {
         let _header = self._header.with_offset(left, right)?;
         let mut _lines = self._lines;
@@ -112,15 +223,395 @@ impl Hunk {
         num_added - num_removed
     }
 
-    pub fn fuse(self, other: Hunk) -> Result<Hunk, MergeErr> {
-        if !self.header().should_fuse(other.header()) {
+    // Trims the context around the changes to at most num lines, splitting
+    // the hunk where more than 2 * num unchanged lines separate changes,
+    // like 'diff -U num' would. A hunk without changes disappears.
+    pub fn trim_context(self, num: usize) -> Vec<Hunk> {
+        let [[old_first, _], [new_first, _]] = self._header.spans();
+        let mut lines = self._lines;
+        lines.remove(0);
+
+        // line numbers of each line on both sides, where a side that the
+        // line is not on gets the number of the next line on it
+        let mut positions: Vec<(i64, i64)> = Vec::new();
+        let mut changes: Vec<usize> = Vec::new();
+        let (mut old, mut new) = (old_first, new_first);
+        for (i, line) in lines.iter().enumerate() {
+            positions.push((old, new));
+            match line.chars().next() {
+                Some('-') => old += 1,
+                Some('+') => new += 1,
+                // the '\\' line after a change belongs to it
+                Some('\\') if i > 0 && changes.last() == Some(&(i - 1)) => {}
+                Some('\\') => continue,
+                _ => {
+                    old += 1;
+                    new += 1;
+                    continue;
+                }
+            }
+            changes.push(i);
+        }
+
+        // ranges of changes close enough to stay in one hunk
+        let mut groups: Vec<[usize; 2]> = Vec::new();
+        for i in changes {
+            match groups.last_mut() {
+                Some(group) if i - group[1] - 1 <= 2 * num => group[1] = i,
+                _ => groups.push([i, i]),
+            }
+        }
+
+        let mut hunks: Vec<Hunk> = Vec::new();
+        for [first, last] in groups {
+            let first = first.saturating_sub(num);
+            let mut last = (last + num).min(lines.len() - 1);
+            while lines
+                .get(last + 1)
+                .is_some_and(|line| line.starts_with('\\'))
+            {
+                last += 1;
+            }
+            let (old, new) = positions[first];
+            let (end_old, end_new) = match positions.get(last + 1) {
+                Some(position) => *position,
+                None => (
+                    old_first + self._header.fields[1],
+                    new_first + self._header.fields[3],
+                ),
+            };
+            let (num_removed, num_added) = (end_old - old, end_new - new);
+            let header: Header = [
+                old - if num_removed == 0 { 1 } else { 0 },
+                num_removed,
+                new - if num_added == 0 { 1 } else { 0 },
+                num_added,
+            ]
+            .into();
+            let header = header.with_heading(&self._header.heading);
+            hunks.push(Hunk::new(header, lines[first..=last].to_vec()));
+        }
+        hunks
+    }
+
+    // The lines of the '-' side and of the '+' side, without the prefixes
+    pub fn texts(&self) -> [Vec<&str>; 2] {
+        let text = |skip: char| {
+            self._lines[1..]
+                .iter()
+                .filter(|line| {
+                    !line.starts_with(skip) && !line.starts_with('\\')
+                })
+                .map(|line| text(line))
+                .collect()
+        };
+        [text('+'), text('-')]
+    }
+
+    // Whether the '-' and the '+' side end without a newline, as marked by
+    // a '\\' line after their last line
+    pub fn missing_eols(&self) -> [bool; 2] {
+        let mut missing = [false, false];
+        for pair in self._lines[1..].windows(2) {
+            if pair[1].starts_with('\\') {
+                match pair[0].chars().next() {
+                    Some('-') => missing[0] = true,
+                    Some('+') => missing[1] = true,
+                    _ => missing = [true, true],
+                }
+            }
+        }
+        missing
+    }
+
+    // The hunk with a '\\' line after the last line of each side that
+    // misses its newline and none after the others, where a context line
+    // that ends both sides but only one of them with a newline becomes a
+    // '-' and a '+' line
+    pub fn with_missing_eols(self, missing: [bool; 2]) -> Hunk {
+        let mut lines: Vec<String> = self._lines[1..]
+            .iter()
+            .filter(|line| !line.starts_with('\\'))
+            .cloned()
+            .collect();
+        let last = |lines: &[String], skip: char| {
+            lines.iter().rposition(|line| !line.starts_with(skip))
+        };
+
+        let (old_last, new_last) = (last(&lines, '+'), last(&lines, '-'));
+        if let Some(i) = old_last.filter(|_| missing[0] != missing[1]) {
+            if old_last == new_last && lines[i].starts_with(' ') {
+                let line = text(&lines[i]).to_string();
+                lines.splice(i..=i, [format!("-{line}"), format!("+{line}")]);
+            }
+        }
+
+        // from the back, so that the positions still hold
+        let mut marked: Vec<usize> = [(0, '+'), (1, '-')]
+            .into_iter()
+            .filter(|(side, _)| missing[*side])
+            .filter_map(|(_, skip)| last(&lines, skip))
+            .collect();
+        marked.sort();
+        marked.dedup();
+        for i in marked.into_iter().rev() {
+            lines.insert(i + 1, NO_EOL.to_string());
+        }
+        Hunk::new(self._header, lines)
+    }
+
+    // Diffs the old and new text of the hunk again, so that lines which
+    // were removed and added back, e.g. by different inputs of a fusion,
+    // become context
+    pub fn rediff(self) -> Hunk {
+        let [old, new] = self.texts();
+        Hunk::from_texts(self._header.clone(), &old, &new)
+            .with_missing_eols(self.missing_eols())
+    }
+
+    // Checks the '-' and context lines against the base file the diff was
+    // made from, then adds up to [before, after] lines of the base around
+    // the hunk as context
+    pub fn expand_context(
+        self,
+        base: &[String],
+        [before, after]: [usize; 2],
+    ) -> Result<Hunk, ParseErr> {
+        let [[old_first, old_end], [new_first, _]] = self._header.spans();
+        if old_first < 1
+            || old_end < old_first
+            || old_end as usize > base.len() + 1
+        {
+            return Err(parse_err!(
+                "Hunk {} is past the end of the base, which has {} lines",
+                self._header,
+                base.len()
+            ));
+        }
+
+        let mut index = old_first as usize - 1;
+        for line in self._lines.iter().skip(1) {
+            if line.starts_with('+') || line.starts_with('\\') {
+                continue;
+            }
+            let Some(expected) = base.get(index) else {
+                return Err(parse_err!(
+                    "Hunk {} has more lines than its header says",
+                    self._header
+                ));
+            };
+            if text(line) != expected {
+                return Err(parse_err!(
+                    "Hunk {} does not match the base at line {}: expected \
+                    '{}', got '{}'",
+                    self._header,
+                    index + 1,
+                    expected,
+                    text(line)
+                ));
+            }
+            index += 1;
+        }
+
+        let num_before = before.min(old_first as usize - 1);
+        let num_after = after.min(base.len() + 1 - old_end as usize);
+        if num_before + num_after == 0 {
+            return Ok(self);
+        }
+
+        let mut lines: Vec<String> = base
+            [old_first as usize - 1 - num_before..old_first as usize - 1]
+            .iter()
+            .map(|line| format!(" {line}"))
+            .collect();
+        lines.extend(self._lines.into_iter().skip(1));
+        lines.extend(
+            base[old_end as usize - 1..old_end as usize - 1 + num_after]
+                .iter()
+                .map(|line| format!(" {line}")),
+        );
+
+        let num_added = (num_before + num_after) as i64;
+        let header: Header = [
+            old_first - num_before as i64,
+            self._header.fields[1] + num_added,
+            new_first - num_before as i64,
+            self._header.fields[3] + num_added,
+        ]
+        .into();
+        let header = header.with_heading(&self._header.heading);
+        Ok(Hunk::new(header, lines))
+    }
+
+    // Recomputes the header counts from the lines and the '+' start line
+    // from the '-' start line, given the offset of the preceding hunks
+    pub fn recount(&mut self, offset: i64) -> Option<Recount> {
+        let mut counts = (0i64, 0i64);
+        for line in self._lines.iter().skip(1) {
+            match line.chars().next() {
+                Some('-') => counts.0 += 1,
+                Some('+') => counts.1 += 1,
+                Some(' ') => {
+                    counts.0 += 1;
+                    counts.1 += 1;
+                }
+                _ => {}
+            }
+        }
+
+        let before = self._header.clone();
+        let after = before.clone().with_counts(counts, offset);
+        if before == after {
+            return None;
+        }
+
+        self._lines[0] = after.to_string();
+        self._header = after.clone();
+        Some(Recount { before, after })
+    }
+
+    // The hunk that undoes this one. The '-' and '+' lines swap, and each
+    // run of changes keeps its '-' lines first.
+    pub fn reverse(self) -> Hunk {
+        let mut lines: Vec<String> = Vec::new();
+        let mut added: Vec<String> = Vec::new();
+        let mut last = ' ';
+        for line in self._lines.into_iter().skip(1) {
+            let kind = line.chars().next().unwrap_or(' ');
+            match kind {
+                '-' => added.push(format!("+{}", &line[1..])),
+                '+' => lines.push(format!("-{}", &line[1..])),
+                // the '\' line stays with the line before it
+                '\\' if last == '-' => added.push(line),
+                '\\' => lines.push(line),
+                _ => {
+                    lines.append(&mut added);
+                    lines.push(line);
+                }
+            }
+            if kind != '\\' {
+                last = kind;
+            }
+        }
+        lines.append(&mut added);
+        Hunk::new(self._header.reverse(), lines)
+    }
+
+    // Joins a hunk that starts in or right after the trailing context of
+    // this one, keeping the context lines the two share only once
+    pub fn join(self, other: Hunk) -> Result<Hunk, MergeErr> {
+        let [[old_first, old_end], [new_first, new_end]] =
+            self._header.spans();
+        let [[other_old_first, other_old_end], [other_new_first, other_new_end]] =
+            other._header.spans();
+        let num_shared = old_end - other_old_first;
+        if num_shared < 0 || num_shared != new_end - other_new_first {
             return Err(merge_err!(
-                "Expected hunks {} and {} to overlap, but they do not",
-                self,
-                other
+                "Hunks {self} and {other} cannot be joined"
             ));
         }
 
+        // the lines may be fewer than the headers say
+        let num_shared = num_shared as usize;
+        let trailing = self
+            ._lines
+            .len()
+            .checked_sub(num_shared)
+            .and_then(|start| self._lines.get(start..));
+        let leading = other._lines.get(1..1 + num_shared);
+        let (Some(trailing), Some(leading)) = (trailing, leading) else {
+            return Err(merge_err!(
+                "Hunks {self} and {other} cannot be joined"
+            ));
+        };
+        if trailing != leading
+            || trailing.iter().any(|line| !line.starts_with(' '))
+        {
+            return Err(merge_err!(
+                "Hunks {self} and {other} disagree on the context they share"
+            ));
+        }
+
+        let num_removed = other_old_end.max(old_end) - old_first;
+        let num_added = other_new_end.max(new_end) - new_first;
+        let header: Header = [
+            if num_removed == 0 {
+                self._header.fields[0]
+            } else {
+                old_first
+            },
+            num_removed,
+            if num_added == 0 {
+                self._header.fields[2]
+            } else {
+                new_first
+            },
+            num_added,
+        ]
+        .into();
+        let header = header.with_heading(&self._header.heading);
+
+        let mut lines: Vec<String> = self._lines.into_iter().skip(1).collect();
+        lines.extend(other._lines.into_iter().skip(1 + num_shared));
+        Ok(Hunk::new(header, lines))
+    }
+
+    // Merges a touching hunk of the same file diff that doesn't start
+    // before this one. The other hunk is moved on top of this one, so that
+    // the two can be fused like consecutive diffs, and any line the two
+    // disagree on is reported by the fusion.
+    pub fn merge(self, other: Hunk) -> Result<Hunk, MergeErr> {
+        if self._lines == other._lines {
+            return Ok(self);
+        }
+
+        let [lhs_minus, lhs_plus] = self._header.spans();
+        let [rhs_minus, rhs_plus] = other._header.spans();
+
+        // lines added before the first '-' side line of the other hunk
+        let mut offset = self._header.offset();
+        let mut lineno = lhs_minus[0];
+        for line in self._lines.iter().skip(1) {
+            match line.chars().next() {
+                Some('+') if lineno <= rhs_minus[0] => offset += 1,
+                Some('-') if lineno < rhs_minus[0] => {
+                    offset -= 1;
+                    lineno += 1;
+                }
+                Some('+') => {}
+                _ => lineno += 1,
+            }
+        }
+
+        let headers = vec![self._header.clone(), other._header.clone()];
+        let other = other.with_offset(offset, 0)?;
+        let hunk = fuse(
+            self._header.fuse(&other._header),
+            info_source::InfoSource::new(self, other),
+        )
+        .map_err(|err| err.with_headers(headers.clone()))?;
+
+        let [_, num_removed, _, num_added] = hunk._header.fields;
+        if num_removed
+            != lhs_minus[1].max(rhs_minus[1]) - lhs_minus[0].min(rhs_minus[0])
+            || num_added
+                != lhs_plus[1].max(rhs_plus[1]) - lhs_plus[0].min(rhs_plus[0])
+        {
+            return Err(merge_err!(
+                "Hunks disagree on the lines they share, merged into {hunk}"
+            )
+            .with_headers(headers));
+        }
+
+        Ok(hunk)
+    }
+
+    pub fn fuse(self, other: Hunk) -> Result<Hunk, MergeErr> {
+        if !self.header().should_fuse(other.header()) {
+            return Err(MergeErr::new(MergeErrKind::NoOverlap)
+                .with_headers(vec![self._header, other._header]));
+        }
+
         fuse(
             self._header.fuse(&other._header),
             info_source::InfoSource::new(self, other),
@@ -130,7 +621,7 @@ impl Hunk {
 
 impl std::fmt::Display for Hunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(line) = self._lines.get(0) {
+        if let Some(line) = self._lines.first() {
             write!(f, "{}", &line)
         } else {
             write!(f, "[no lines; header = {:?}]", self._header)
@@ -161,6 +652,24 @@ mod tests {
         }
     }
 
+    fn test_err(left: &str, right: &str) {
+        let lhunk = Hunk::from_lines(&mut left.lines().peekable()).unwrap();
+        let rhunk = Hunk::from_lines(&mut right.lines().peekable()).unwrap();
+        assert!(lhunk.fuse(rhunk).is_err());
+    }
+
+    fn test_join(left: &str, right: &str, expected: Result<&str, ()>) {
+        let lhunk = Hunk::from_lines(&mut left.lines().peekable()).unwrap();
+        let rhunk = Hunk::from_lines(&mut right.lines().peekable()).unwrap();
+        match expected {
+            Ok(expected) => assert_eq!(
+                lhunk.join(rhunk).unwrap().lines(),
+                &expected.lines().collect::<Vec<&str>>()
+            ),
+            Err(()) => assert!(lhunk.join(rhunk).is_err()),
+        }
+    }
+
     #[test]
     fn case_1() {
         test(
@@ -242,7 +751,9 @@ mod tests {
 
     #[test]
     fn case_4() {
-        test(
+        // the left hunk follows one that added a line, which the right hunk
+        // takes as a line of the base
+        test_err(
             "\
 @@ -1,0 +3,1 @@
 +d
@@ -253,13 +764,6 @@ mod tests {
  b
  c
  d
-",
-            "\
-@@ -1,2 +1,4 @@
-+a
- b
- c
-+d
 ",
         );
     }
@@ -324,4 +828,115 @@ mod tests {
 ",
         );
     }
+
+    #[test]
+    fn case_8() {
+        let hunk = Hunk::from_lines(
+            &mut "@@ -1,4 +1,4 @@\n a\n\n-b\n+c\n\n".lines().peekable(),
+        )
+        .unwrap();
+        assert_eq!(
+            hunk.lines(),
+            &["@@ -1,4 +1,4 @@", " a", " ", "-b", "+c", " "]
+        );
+    }
+
+    #[test]
+    fn case_9() {
+        let mut lines = "@@ -1 +1 @@\n-a\n+b\n\nc".lines().peekable();
+        let hunk = Hunk::from_lines(&mut lines).unwrap();
+        assert_eq!(hunk.lines(), &["@@ -1 +1 @@", "-a", "+b"]);
+        assert_eq!(lines.next(), Some(""));
+    }
+
+    #[test]
+    fn join_case_1() {
+        test_join(
+            "@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n d",
+            "@@ -3,4 +3,3 @@\n c\n d\n-e\n f",
+            Ok("@@ -1,6 +1,5 @@\n-a\n+A\n b\n c\n d\n-e\n f"),
+        );
+    }
+
+    #[test]
+    fn join_case_2() {
+        test_join(
+            "@@ -1,2 +1,2 @@\n-a\n+A\n b",
+            "@@ -3 +2,0 @@\n-c",
+            Ok("@@ -1,3 +1,2 @@\n-a\n+A\n b\n-c"),
+        );
+    }
+
+    #[test]
+    fn join_case_3() {
+        test_join(
+            "@@ -1,3 +1,3 @@\n-a\n+A\n b\n c",
+            "@@ -3,2 +3,2 @@\n x\n-d\n+D",
+            Err(()),
+        );
+    }
+
+    #[test]
+    fn reverse_case_1() {
+        let hunk = Hunk::from_lines(
+            &mut "@@ -1,5 +1,4 @@\n 1\n-2\n-3\n+X\n 4\n+Y\n-5"
+                .lines()
+                .peekable(),
+        )
+        .unwrap();
+        assert_eq!(
+            hunk.reverse().lines(),
+            &["@@ -1,4 +1,5 @@", " 1", "-X", "+2", "+3", " 4", "-Y", "+5"]
+        );
+    }
+
+    #[test]
+    fn reverse_case_2() {
+        let hunk = Hunk::from_lines(
+            &mut "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b"
+                .lines()
+                .peekable(),
+        )
+        .unwrap();
+        assert_eq!(
+            hunk.reverse().lines(),
+            &[
+                "@@ -1,2 +1,2 @@",
+                " a",
+                "-b",
+                "+b",
+                "\\ No newline at end of file"
+            ]
+        );
+    }
+
+    #[test]
+    fn rediff_case_1() {
+        let mut lines =
+            "@@ -1,3 +1,3 @@\n-a\n-b\n-c\n+a\n+B\n+c".lines().peekable();
+        let hunk = Hunk::from_lines(&mut lines).unwrap();
+        assert_eq!(
+            hunk.rediff().lines(),
+            &["@@ -1,3 +1,3 @@", " a", "-b", "+B", " c"]
+        );
+    }
+
+    #[test]
+    fn rediff_case_2() {
+        let mut lines =
+            "@@ -1,2 +1,2 @@\n-a\n-b\n+a\n+b\n\\ No newline at end of file"
+                .lines()
+                .peekable();
+        let hunk = Hunk::from_lines(&mut lines).unwrap();
+        assert_eq!(
+            hunk.rediff().lines(),
+            &[
+                "@@ -1,2 +1,2 @@",
+                " a",
+                "-b",
+                "+b",
+                "\\ No newline at end of file"
+            ]
+        );
+    }
 }
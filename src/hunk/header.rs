@@ -1,41 +1,59 @@
-use crate::error::{MergeErr, ParseErr};
-use crate::macros::{merge_err, parse_err};
+use crate::error::{MergeErr, ParseErr, ParseErrKind};
+use crate::macros::merge_err;
 use core::cmp::{min, Ordering};
 
 const NUM_FIELDS: usize = 4;
 
-#[derive(Clone, Debug, PartialEq)]
+// The largest line number or count in a hunk header, well within i64 so
+// that adding up the fields and offsets of headers can't overflow
+const MAX_FIELD: i64 = i32::MAX as i64;
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Header {
     pub fields: [i64; NUM_FIELDS],
+    // the text after the closing '@@', e.g. ' fn main() {' for the section
+    // heading that git adds
+    pub heading: String,
 }
 
 impl From<[i64; NUM_FIELDS]> for Header {
     fn from(fields: [i64; NUM_FIELDS]) -> Header {
-        Header { fields }
-    }
-}
-
-impl Default for Header {
-    fn default() -> Header {
         Header {
-            fields: [0, 0, 0, 0],
+            fields,
+            heading: String::new(),
         }
     }
 }
 
 impl std::fmt::Display for Header {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        let [mmin, mnum, pmin, pnum] = self.fields;
+        match [mnum, pnum] {
+            [1, 1] => write!(f, "@@ -{mmin} +{pmin} @@"),
+            [_, 1] => write!(f, "@@ -{mmin},{mnum} +{pmin} @@"),
+            [1, _] => write!(f, "@@ -{mmin} +{pmin},{pnum} @@"),
+            _ => write!(f, "@@ -{mmin},{mnum} +{pmin},{pnum} @@"),
+        }?;
+        write!(f, "{}", self.heading)
     }
 }
 
 impl Header {
     pub fn parse(header: &str) -> Result<Header, ParseErr> {
-        let group_iter = header
+        // a diff with CRLF line endings throughout
+        let header = header.strip_suffix('\r').unwrap_or(header);
+        let bad_header = |msg: &str| {
+            ParseErr::new(ParseErrKind::BadHeader(msg.to_string()))
+                .at_line(header)
+        };
+        let (groups, heading) = header
             .strip_prefix("@@ ")
-            .map_or(None, |s| s.strip_suffix(" @@")) // keep the last ' '
-            .ok_or(parse_err!("Unexpected header format in '{header}'"))?
-            .split(' ');
+            .and_then(|s| s.split_once(" @@")) // keep the last ' '
+            .filter(|(_, heading)| {
+                heading.is_empty() || heading.starts_with(' ')
+            })
+            .ok_or_else(|| bad_header("Unexpected hunk header format"))?;
+        let group_iter = groups.split(' ');
 
         let mut result: [i64; NUM_FIELDS] = [1; NUM_FIELDS];
         let mut i: usize = 0;
@@ -44,51 +62,59 @@ impl Header {
             for (j, field) in group.split(',').enumerate() {
                 let index = i + j;
                 if index >= NUM_FIELDS {
-                    return Err(parse_err!(
-                        "Too many header fields in '{header}'"
-                    ));
+                    return Err(bad_header("Too many hunk header fields"));
                 }
                 result[index] = field
                     .trim_start_matches(['-', '+'])
                     .parse::<i64>()
-                    .map_err(|_| {
-                        parse_err!(
-                            "Invalid header field '{field}' in '{header}'"
-                        )
+                    .ok()
+                    .filter(|value| *value <= MAX_FIELD)
+                    .ok_or_else(|| {
+                        bad_header(&format!(
+                            "Invalid hunk header field '{field}'"
+                        ))
                     })?;
             }
             i += 2;
         }
 
         if i <= 2 {
-            return Err(parse_err!("Too few header fields in '{header}'"));
+            return Err(bad_header("Too few hunk header fields"));
         }
 
-        Ok(result.into())
+        Ok(Header {
+            fields: result,
+            heading: heading.to_string(),
+        })
+    }
+
+    pub fn with_heading(mut self, heading: &str) -> Header {
+        self.heading = heading.to_string();
+        self
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn cmp(&self, other: &Header) -> Ordering {
         let [lhs_mmin, _, lhs_pmin, _] = self.fields;
         let [rhs_mmin, _, rhs_pmin, _] = other.fields;
         min(lhs_mmin, lhs_pmin).cmp(min(&rhs_mmin, &rhs_pmin))
     }
 
+    // The first lines of both sides of the hunk that this one fuses into
+    // with the other, a hunk of the diff that follows, where an empty side
+    // is placed after the line given in the header, as in spans. The counts
+    // are left for the fusion to fill in, see LineCounter::update_header.
     pub fn fuse(&self, other: &Header) -> Header {
-        let left = match [self.fields[1], other.fields[1]] {
-            [0, 0] => min(self.fields[0], other.fields[0] - self.offset()),
-            [_, 0] => self.fields[0],
-            [0, _] => other.fields[0],
-            _ => min(self.fields[0], other.fields[0] - self.offset()),
-        };
-
-        let right = match [self.fields[3], other.fields[3]] {
-            [0, 0] => min(self.fields[2] + other.offset(), other.fields[2]),
-            [_, 0] => self.fields[2],
-            [0, _] => other.fields[2],
-            _ => min(self.fields[2] + other.offset(), other.fields[2]),
-        };
-
-        [left, 0, right, 0].into()
+        let [[old_first, _], [new_first, _]] = self.spans();
+        let [[other_old_first, _], [other_new_first, _]] = other.spans();
+        let fused: Header = [
+            min(old_first, other_old_first - self.offset()),
+            0,
+            min(new_first + other.offset(), other_new_first),
+            0,
+        ]
+        .into();
+        fused.with_heading(&self.heading)
     }
 
     pub fn overlaps(&self, other: &Header) -> bool {
@@ -108,20 +134,36 @@ impl Header {
         false
     }
 
-    pub fn should_fuse(&self, other: &Header) -> bool {
-        let [lhs_min, lhs_max] = other.minus_range();
-        let [rhs_min, rhs_max] = self.plus_range();
-        lhs_min < rhs_max && rhs_min < lhs_max
+    // Whether the line ranges overlap or are adjacent on both sides
+    pub fn touches(&self, other: &Header) -> bool {
+        let [lhs_minus, lhs_plus] = self.spans();
+        let [rhs_minus, rhs_plus] = other.spans();
+        lhs_minus[0] <= rhs_minus[1]
+            && rhs_minus[0] <= lhs_minus[1]
+            && lhs_plus[0] <= rhs_plus[1]
+            && rhs_plus[0] <= lhs_plus[1]
     }
 
-    pub fn to_string(&self) -> String {
-        let [mmin, mnum, pmin, pnum] = self.fields;
-        match [mnum, pnum] {
-            [1, 1] => format!("@@ -{mmin} +{pmin} @@"),
-            [_, 1] => format!("@@ -{mmin},{mnum} +{pmin} @@"),
-            [1, _] => format!("@@ -{mmin} +{pmin},{pnum} @@"),
-            _ => format!("@@ -{mmin},{mnum} +{pmin},{pnum} @@"),
-        }
+    // [first, last + 1] line numbers of the '-' and '+' sides, where an
+    // empty side is placed after the line given in the header
+    pub fn spans(&self) -> [[i64; 2]; 2] {
+        let span = |start: i64, num: i64| {
+            let first = start + if num == 0 { 1 } else { 0 };
+            [first, first + num]
+        };
+        [
+            span(self.fields[0], self.fields[1]),
+            span(self.fields[2], self.fields[3]),
+        ]
+    }
+
+    // Whether the '+' side of this hunk and the '-' side of the other,
+    // which are both of the file in between, share lines, or an empty one
+    // falls between the lines of the other
+    pub fn should_fuse(&self, other: &Header) -> bool {
+        let [_, [lhs_first, lhs_end]] = self.spans();
+        let [[rhs_first, rhs_end], _] = other.spans();
+        lhs_first < rhs_end && rhs_first < lhs_end
     }
 
     pub fn with_offset(
@@ -140,11 +182,27 @@ impl Header {
         Ok(self)
     }
 
+    pub fn with_counts(mut self, counts: (i64, i64), offset: i64) -> Header {
+        self.fields[1] = counts.0;
+        self.fields[3] = counts.1;
+        // a zero count means the start line is the one before the hunk
+        self.fields[2] =
+            self.fields[0] + offset + if self.fields[1] == 0 { 1 } else { 0 }
+                - if self.fields[3] == 0 { 1 } else { 0 };
+        self
+    }
+
+    // The header of the hunk that undoes this one
+    pub fn reverse(&self) -> Header {
+        let [mmin, mnum, pmin, pnum] = self.fields;
+        Header::from([pmin, pnum, mmin, mnum]).with_heading(&self.heading)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.fields[1] == 0 && self.fields[3] == 0
     }
 
-    fn offset(&self) -> i64 {
+    pub fn offset(&self) -> i64 {
         // how many lines were added before this hunk
         (self.fields[2] + if self.fields[3] == 0 { 1 } else { 0 })
             - (self.fields[0] + if self.fields[1] == 0 { 1 } else { 0 })
@@ -168,7 +226,7 @@ mod tests {
     }
 
     fn failure(string: &str) {
-        assert_eq!(true, Header::parse(string).is_err());
+        assert!(Header::parse(string).is_err());
     }
 
     #[test]
@@ -225,4 +283,19 @@ mod tests {
     fn case_11() {
         failure("@@ 1 2 3 4 @@");
     }
+
+    #[test]
+    fn case_12() {
+        success("@@ -2147483647,0 +1 @@", [2147483647, 0, 1, 1]);
+        failure("@@ -2147483648,0 +1 @@");
+        failure("@@ -9223372036854775807,1 +1 @@");
+    }
+
+    #[test]
+    fn case_13() {
+        let header = Header::parse("@@ -1,2 +1,2 @@ fn main() {").unwrap();
+        assert_eq!(header.fields, [1, 2, 1, 2]);
+        assert_eq!(header.to_string(), "@@ -1,2 +1,2 @@ fn main() {");
+        failure("@@ -1,2 +1,2 @@fn main() {");
+    }
 }
@@ -1,6 +1,6 @@
-use crate::fuse::line::Line;
 use crate::fuse::info_iter::InfoIter;
 use crate::fuse::info_source::InfoSource as Trait;
+use crate::fuse::line::Line;
 
 use crate::hunk::Hunk;
 
@@ -3,14 +3,66 @@ use std::path::{Path, PathBuf};
 
 use crate::macros::debugln;
 
+const COMMANDS: [&str; 4] = ["recount", "apply", "reverse", "interdiff"];
+
+fn has_arg(names: &[&str]) -> bool {
+    env::args().skip(1).any(|arg| names.contains(&arg.as_str()))
+}
+
 pub fn has_help_arg() -> bool {
-    let predicate = |arg: &String| arg == "-h" || arg == "--help";
-    env::args().find(predicate).is_some()
+    has_arg(&["-h", "--help"])
+}
+
+pub fn has_lenient_arg() -> bool {
+    has_arg(&["--lenient"])
+}
+
+pub fn has_no_prefix_arg() -> bool {
+    has_arg(&["--no-prefix"])
+}
+
+pub fn has_minimal_arg() -> bool {
+    has_arg(&["--minimal"])
+}
+
+pub fn has_verify_arg() -> bool {
+    has_arg(&["--verify"])
+}
+
+pub fn has_reverse_arg() -> bool {
+    has_arg(&["-R", "--reverse"])
+}
+
+pub fn has_dry_run_arg() -> bool {
+    has_arg(&["--dry-run"])
+}
+
+pub fn has_conflicts_arg() -> bool {
+    has_arg(&["--conflicts"])
+}
+
+pub fn has_report_arg() -> bool {
+    has_arg(&["--report"])
+}
+
+// Value of an argument given as --name=value
+pub fn get_value(name: &str) -> Option<String> {
+    let prefix = format!("{name}=");
+    env::args()
+        .skip(1)
+        .find_map(|arg| arg.strip_prefix(&prefix).map(|s| s.to_string()))
+}
+
+pub fn get_command() -> Option<String> {
+    env::args()
+        .nth(1)
+        .filter(|arg| COMMANDS.contains(&arg.as_str()))
 }
 
 pub fn get_paths() -> Vec<PathBuf> {
     let mut paths: Vec<PathBuf> = Vec::new();
-    for arg in env::args().skip(1) {
+    let skip = if get_command().is_some() { 2 } else { 1 };
+    for arg in env::args().skip(skip).filter(|arg| !arg.starts_with('-')) {
         paths.push(Path::new(&arg).into());
     }
     debugln!("Received {} paths from input", paths.len());
@@ -1,9 +1,16 @@
+pub mod apply;
 pub mod diff;
+pub mod error;
 pub mod file_diff;
+pub mod generate;
 pub mod hunk;
 pub mod input;
+pub mod options;
+pub mod path;
+pub mod verify;
+pub mod write;
 
-mod error;
 mod fuse;
 mod header;
 mod macros;
+mod myers;
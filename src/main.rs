@@ -1,26 +1,311 @@
-use diff_fuse::diff::Diff;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use diff_fuse::apply;
+use diff_fuse::diff::{Diff, Order};
 use diff_fuse::input;
+use diff_fuse::options::{FuseOptions, Matching, OnFailure, ParseOptions};
+use diff_fuse::verify;
+use diff_fuse::write;
+
+// Prints the error, if any, and exits
+fn or_exit<T, E: Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    })
+}
+
+fn read(path: &PathBuf, options: &ParseOptions) -> Diff {
+    let diff = or_exit(Diff::read_with(path, options));
+    for skipped in diff.skipped() {
+        eprintln!("Warning: {}: {skipped}", path.display());
+    }
+    diff
+}
+
+fn fuse(paths: Vec<PathBuf>, options: &ParseOptions) {
+    let inputs: Vec<Diff> =
+        paths.iter().map(|path| read(path, options)).collect();
+    let on_failure =
+        or_exit(match input::get_value("--on-failure").as_deref() {
+            None | Some("fail") => Ok(OnFailure::Fail),
+            Some("sequential") => Ok(OnFailure::Sequential),
+            Some("omit") => Ok(OnFailure::Omit),
+            Some(value) => Err(format!(
+                "Expected fail, sequential or omit in --on-failure={value}"
+            )),
+        });
+    let matching = or_exit(match input::get_value("--matching").as_deref() {
+        None | Some("exact") => Ok(Matching::Exact),
+        Some("ignore-eol") => Ok(Matching::IgnoreEol),
+        Some("ignore-trailing-space") => Ok(Matching::IgnoreTrailingSpace),
+        Some("ignore-all-space") => Ok(Matching::IgnoreAllSpace),
+        Some(value) => Err(format!(
+            "Expected exact, ignore-eol, ignore-trailing-space or \
+            ignore-all-space in --matching={value}"
+        )),
+    });
+    let options = FuseOptions {
+        conflicts: input::has_conflicts_arg(),
+        on_failure,
+        matching,
+    };
+
+    // the input each failure came with, for the summary
+    let mut failed_inputs: Vec<&PathBuf> = Vec::new();
+    let mut input_iter = inputs.clone().into_iter().zip(paths.iter());
+    let Some((mut diff, _)) = input_iter.next() else {
+        return;
+    };
+    for (other, path) in input_iter {
+        let result = diff.fuse_with(other, &options);
+        diff = or_exit(result.map_err(|err| err.with_path(path)));
+        failed_inputs.resize(diff.failed().len(), path);
+    }
+    for (file_name, loose_match) in diff.loose_matches() {
+        eprintln!("Warning: {file_name}: {loose_match}");
+    }
+
+    if input::has_verify_arg() {
+        let failures = verify::verify(&inputs, &diff);
+        for failure in failures.iter() {
+            eprintln!("Verification failed: {failure}");
+        }
+        if !failures.is_empty() {
+            std::process::exit(1);
+        }
+    }
+
+    print(&mut diff, input::has_minimal_arg());
+
+    if !diff.failed().is_empty() {
+        let action = match on_failure {
+            OnFailure::Omit => "omitted",
+            _ => "kept as the inputs' diffs",
+        };
+        eprintln!(
+            "{} file(s) could not be fused, {action}:",
+            diff.failed().len()
+        );
+        eprintln!("input\tfile\terror");
+        for (failed, path) in diff.failed().iter().zip(failed_inputs) {
+            eprintln!(
+                "{}\t{}\t{}",
+                path.display(),
+                failed.file_name,
+                failed.err
+            );
+        }
+        std::process::exit(2);
+    }
+
+    let num_conflicts = diff.num_conflicts();
+    if num_conflicts > 0 {
+        eprintln!(
+            "{num_conflicts} conflict(s) between the inputs, see the blocks \
+            marked '<<<<<<<'"
+        );
+        std::process::exit(1);
+    }
+}
+
+// Prints the diff, re-diffed if minimal, see --minimal
+fn print(diff: &mut Diff, minimal: bool) {
+    // the whole file with --unified=all, as far as the base is known
+    let num = match input::get_value("--unified").as_deref() {
+        None => None,
+        Some("all") => Some(usize::MAX),
+        Some(value) => Some(or_exit(value.parse::<usize>().map_err(|_| {
+            format!("Expected a number of lines in --unified={value}")
+        }))),
+    };
+
+    if let Some(base_dir) = input::get_value("--base-dir") {
+        or_exit(
+            diff.expand_context(&PathBuf::from(base_dir), num.unwrap_or(3)),
+        );
+    }
+
+    // re-diffing leaves the unchanged lines of whole hunks as context,
+    // which is cut down to what svn diff would show unless told otherwise
+    let num = if minimal {
+        diff.rediff();
+        num.or(Some(3))
+    } else {
+        num
+    };
+
+    if let Some(num) = num.filter(|num| *num != usize::MAX) {
+        diff.trim_context(num);
+    }
+
+    diff.sort(or_exit(match input::get_value("--order").as_deref() {
+        None | Some("first-seen") => Ok(Order::FirstSeen),
+        Some("lexicographic") => Ok(Order::Lexicographic),
+        Some("tree") => Ok(Order::Tree),
+        Some(value) => Err(format!(
+            "Expected first-seen, lexicographic or tree in --order={value}"
+        )),
+    }));
+
+    match input::get_value("--format").as_deref() {
+        Some("git") => {
+            let mut options = write::git::Options::default();
+            if input::has_no_prefix_arg() {
+                options.old_prefix.clear();
+                options.new_prefix.clear();
+            }
+            if let Some(prefix) = input::get_value("--src-prefix") {
+                options.old_prefix = prefix;
+            }
+            if let Some(prefix) = input::get_value("--dst-prefix") {
+                options.new_prefix = prefix;
+            }
+            for line in write::git::lines(diff, &options) {
+                println!("{line}");
+            }
+        }
+        Some("svn") => {
+            let options = write::svn::Options {
+                old_revision: input::get_value("--old-revision"),
+                new_revision: input::get_value("--new-revision"),
+            };
+            for line in write::svn::lines(diff, &options) {
+                println!("{line}");
+            }
+        }
+        None => {
+            for line in diff.line_iter() {
+                println!("{line}");
+            }
+        }
+        Some(value) => {
+            eprintln!("Expected git or svn in --format={value}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn recount(paths: Vec<PathBuf>, options: &ParseOptions) {
+    let options = ParseOptions {
+        recount: true,
+        ..options.clone()
+    };
+
+    for path in paths {
+        let mut diff = read(&path, &options);
+        for (file_name, recount) in or_exit(diff.recount()) {
+            eprintln!("{}: {file_name}: {recount}", path.display());
+        }
+
+        print(&mut diff, input::has_minimal_arg());
+    }
+}
+
+// Prints the diff that undoes the fusion of the inputs
+fn reverse(paths: Vec<PathBuf>, options: &ParseOptions) {
+    let mut input_iter = paths.iter().map(|path| (read(path, options), path));
+    let Some((mut diff, _)) = input_iter.next() else {
+        return;
+    };
+    for (other, path) in input_iter {
+        diff = or_exit(diff.fuse(other).map_err(|err| err.with_path(path)));
+    }
+    let mut diff = diff.reverse();
+    print(&mut diff, input::has_minimal_arg());
+}
+
+// Prints what changed between two versions of a patch, which is re-diffed
+// so that only the differences show
+fn interdiff(paths: Vec<PathBuf>, options: &ParseOptions) {
+    let [v1, v2] = match &paths[..] {
+        [v1, v2] => [read(v1, options), read(v2, options)],
+        _ => {
+            println!("Expected two versions of a patch");
+            return;
+        }
+    };
+    let mut diff = or_exit(Diff::interdiff(v1, v2));
+    print(&mut diff, true);
+}
+
+fn apply(paths: Vec<PathBuf>, options: &ParseOptions) {
+    let dir = PathBuf::from(input::get_value("--dir").unwrap_or(".".into()));
+    let mut apply_options = apply::Options {
+        reverse: input::has_reverse_arg(),
+        dry_run: input::has_dry_run_arg(),
+        conflicts: input::has_conflicts_arg(),
+        ..apply::Options::default()
+    };
+    for (name, value) in [
+        ("--max-offset", &mut apply_options.max_offset),
+        ("--fuzz", &mut apply_options.fuzz),
+    ] {
+        if let Some(arg) = input::get_value(name) {
+            *value = or_exit(arg.parse::<usize>().map_err(|_| {
+                format!("Expected a number of lines in {name}={arg}")
+            }));
+        }
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let diff = read(&path, options);
+        for report in or_exit(apply::apply(&diff, &dir, &apply_options)) {
+            if input::has_report_arg() {
+                println!("{}\t{}", path.display(), report.record());
+            } else if apply_options.dry_run {
+                println!("{}: {report}", path.display());
+            } else if report.result.is_err() {
+                eprintln!("{}: {report}", path.display());
+            }
+            failed |= report.result.is_err();
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
 
 fn main() {
     if input::has_help_arg() {
-        println!("Usage: ./diff-fuse [-h, --help] path ...");
+        println!(
+            "Usage: ./diff-fuse [-h, --help] [recount] [--lenient] \
+            [--format=git [--src-prefix=PREFIX] [--dst-prefix=PREFIX] \
+            [--no-prefix]] [--format=svn [--old-revision=REV] \
+            [--new-revision=REV]] \
+            [--order=first-seen|lexicographic|tree] [--unified=N|all] \
+            [--base-dir=DIR] [--minimal] [--verify] [--conflicts] \
+            [--on-failure=fail|sequential|omit] \
+            [--matching=exact|ignore-eol|ignore-trailing-space|\
+            ignore-all-space] path ...\n       \
+            ./diff-fuse reverse [options as above] path ...\n       \
+            ./diff-fuse interdiff [options as above] v1 v2\n       \
+            ./diff-fuse apply [--lenient] [--dir=DIR] [-R, --reverse] \
+            [--dry-run] [--max-offset=N] [--fuzz=N] [--conflicts] \
+            [--report] path ..."
+        );
         return;
     }
 
+    let options = ParseOptions {
+        lenient: input::has_lenient_arg(),
+        ..ParseOptions::default()
+    };
+
     let paths = input::get_paths();
-    if paths.len() < 1 {
+    if paths.is_empty() {
         println!("Expected at least one path");
         return;
     }
 
-    let mut path_iter = paths.into_iter();
-    let first_path = path_iter.next().unwrap();
-    let diff = path_iter
-        .fold(Diff::read(&first_path).unwrap(), |diff, path| {
-            diff.fuse(Diff::read(&path).unwrap()).unwrap()
-        });
-
-    for line in diff.line_iter() {
-        println!("{line}");
+    match input::get_command().as_deref() {
+        Some("recount") => recount(paths, &options),
+        Some("apply") => apply(paths, &options),
+        Some("reverse") => reverse(paths, &options),
+        Some("interdiff") => interdiff(paths, &options),
+        _ => fuse(paths, &options),
     }
 }
@@ -0,0 +1,133 @@
+// Shortest edit script between two sequences, after E. Myers, "An O(ND)
+// Difference Algorithm and Its Variations"
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    Keep,
+    Remove,
+    Add,
+}
+
+// The edits that turn old into new, where removals come before additions
+// in a changed region, as in the output of diff
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    // the common prefix and suffix need no search
+    let prefix = old.iter().zip(new).take_while(|(x, y)| x == y).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut edits = vec![Edit::Keep; prefix];
+    edits.extend(search(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    ));
+    edits.extend(vec![Edit::Keep; suffix]);
+    edits
+}
+
+fn search<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let (n, m) = (old.len() as i64, new.len() as i64);
+    let max = n + m;
+    let index = |k: i64| (k + max + 1) as usize;
+
+    // furthest x reached on each diagonal k = x - y, and for every number
+    // of edits d, the diagonals -d..=d as they were before step d
+    let mut furthest = vec![0i64; 2 * max as usize + 3];
+    let mut trace: Vec<Vec<i64>> = Vec::new();
+    'outer: for d in 0..=max {
+        trace.push(furthest[index(-d)..=index(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d
+                || (k != d && furthest[index(k - 1)] < furthest[index(k + 1)])
+            {
+                furthest[index(k + 1)]
+            } else {
+                furthest[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index(k)] = x;
+            if x >= n && y >= m {
+                break 'outer;
+            }
+        }
+    }
+
+    let mut edits: Vec<Edit> = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, furthest) in trace.iter().enumerate().rev() {
+        let d = d as i64;
+        let get = |k: i64| furthest[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { get(prev_k) };
+        let prev_y = if d == 0 { 0 } else { prev_x - prev_k };
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == prev_x { Edit::Add } else { Edit::Remove });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Edit};
+
+    fn test(old: &str, new: &str, expected: &str) {
+        let old: Vec<char> = old.chars().collect();
+        let new: Vec<char> = new.chars().collect();
+        let actual: String = diff(&old, &new)
+            .iter()
+            .map(|edit| match edit {
+                Edit::Keep => ' ',
+                Edit::Remove => '-',
+                Edit::Add => '+',
+            })
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn case_1() {
+        test("abcabba", "cbabac", "-- +  - +");
+    }
+
+    #[test]
+    fn case_2() {
+        test("", "ab", "++");
+    }
+
+    #[test]
+    fn case_3() {
+        test("ab", "", "--");
+    }
+
+    #[test]
+    fn case_4() {
+        test("abcd", "axyd", " --++ ");
+    }
+
+    #[test]
+    fn case_5() {
+        test("abc", "abc", "   ");
+    }
+}
@@ -0,0 +1,43 @@
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    // skip text that does not belong to any file diff instead of failing
+    pub lenient: bool,
+    // don't validate the hunk headers, they are recomputed by recount
+    pub recount: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FuseOptions {
+    // write lines the diffs disagree on into the fused hunk as a conflict
+    // block instead of failing, see fuse::core::CONFLICT_MARKERS
+    pub conflicts: bool,
+    // what to do with a file that cannot be fused, see Diff::failed
+    pub on_failure: OnFailure,
+    // when the lines that both diffs have of the file in between match
+    pub matching: Matching,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OnFailure {
+    // fail the whole fusion
+    #[default]
+    Fail,
+    // keep the file diffs of the inputs, to be applied one after another
+    Sequential,
+    // leave the file out
+    Omit,
+}
+
+// How lines of the file in between are compared, for diffs made with
+// whitespace ignored, e.g. by svn diff -x -w
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Matching {
+    #[default]
+    Exact,
+    // ignore a trailing '\r', i.e. CRLF vs LF line endings
+    IgnoreEol,
+    // ignore whitespace at the end of the lines, '\r' included
+    IgnoreTrailingSpace,
+    // ignore whitespace altogether, like diff -w
+    IgnoreAllSpace,
+}
@@ -0,0 +1,261 @@
+use crate::error::ParseErr;
+use crate::macros::parse_err;
+
+// Path of a file in a diff header, decoded and with the a/ and b/ style
+// prefixes removed, so that the same file has the same path whatever tool
+// produced the diff
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FilePath {
+    _path: String,
+}
+
+impl FilePath {
+    pub fn new(path: &str) -> FilePath {
+        let mut _path = path.trim_start_matches("./").to_string();
+        while _path.contains("//") {
+            _path = _path.replace("//", "/");
+        }
+        FilePath { _path }
+    }
+
+    // Parses the path in, e.g., a '--- ' line, where None stands for
+    // /dev/null. Also returns the label that follows the path, if any.
+    pub fn parse(field: &str) -> Result<(Option<FilePath>, &str), ParseErr> {
+        let (path, label) = split_label(field)?;
+        if path == "/dev/null" {
+            Ok((None, label))
+        } else {
+            Ok((Some(FilePath::new(&path)), label))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self._path
+    }
+
+    // Removes the first component, e.g. the 'a' in 'a/dir/file'
+    pub fn strip_first(&self) -> Option<FilePath> {
+        let (_, rest) = self._path.split_once('/')?;
+        Some(FilePath::new(rest))
+    }
+
+    pub fn strip_prefix(&self, prefix: &str) -> Option<FilePath> {
+        let rest = self._path.strip_prefix(prefix)?;
+        Some(FilePath::new(rest))
+    }
+}
+
+impl std::fmt::Display for FilePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self._path)
+    }
+}
+
+// Splits a header field into the path and the label that follows it. The
+// label is separated by a tab or, in svn output that went through a
+// terminal, by spaces before a parenthesised revision.
+fn split_label(field: &str) -> Result<(String, &str), ParseErr> {
+    if field.starts_with('"') {
+        let (path, rest) = unquote(field)?;
+        return Ok((path, rest.trim_start()));
+    }
+
+    if let Some((path, label)) = field.split_once('\t') {
+        return Ok((path.to_string(), label));
+    }
+
+    if field.ends_with(')') {
+        if let Some(index) = field.rfind(" (") {
+            let label = &field[index + 1..];
+            return Ok((field[..index].trim_end().to_string(), label));
+        }
+    }
+
+    Ok((field.to_string(), ""))
+}
+
+// Quotes a path the way git does if it contains special characters
+pub fn quote(path: &str) -> String {
+    let special = |c: char| c == '"' || c == '\\' || c.is_control();
+    if !path.chars().any(|c| special(c) || !c.is_ascii()) {
+        return path.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    for byte in path.bytes() {
+        match byte {
+            7 => quoted += "\\a",
+            8 => quoted += "\\b",
+            b'\t' => quoted += "\\t",
+            b'\n' => quoted += "\\n",
+            11 => quoted += "\\v",
+            12 => quoted += "\\f",
+            b'\r' => quoted += "\\r",
+            b'"' => quoted += "\\\"",
+            b'\\' => quoted += "\\\\",
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => quoted += &format!("\\{byte:03o}"),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Decodes a C-style quoted string, as written by git for paths with
+// special characters. Returns the decoded string and the text after the
+// closing quote.
+pub fn unquote(field: &str) -> Result<(String, &str), ParseErr> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut iter = field
+        .strip_prefix('"')
+        .ok_or(parse_err!("Path: Expected a quoted path in '{field}'"))?
+        .char_indices();
+
+    while let Some((i, c)) = iter.next() {
+        let escaped = match c {
+            '"' => {
+                let path = String::from_utf8(bytes).map_err(|_| {
+                    parse_err!("Path: Invalid UTF-8 in '{field}'")
+                })?;
+                return Ok((path, &field[i + 2..]));
+            }
+            '\\' => match iter.next().map(|(_, c)| c) {
+                Some('a') => 7,
+                Some('b') => 8,
+                Some('t') => b'\t',
+                Some('n') => b'\n',
+                Some('v') => 11,
+                Some('f') => 12,
+                Some('r') => b'\r',
+                Some(c @ ('"' | '\\')) => c as u8,
+                Some(c @ '0'..='3') => {
+                    let mut value = c as u32 - '0' as u32;
+                    for _ in 0..2 {
+                        match iter.next().and_then(|(_, c)| c.to_digit(8)) {
+                            Some(digit) => value = value * 8 + digit,
+                            None => {
+                                return Err(parse_err!(
+                                    "Path: Invalid octal escape in '{field}'"
+                                ))
+                            }
+                        }
+                    }
+                    value as u8
+                }
+                _ => {
+                    return Err(parse_err!(
+                        "Path: Invalid escape sequence in '{field}'"
+                    ))
+                }
+            },
+            c => {
+                let mut buffer = [0u8; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                continue;
+            }
+        };
+        bytes.push(escaped);
+    }
+
+    Err(parse_err!("Path: Missing closing quote in '{field}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path::{quote, FilePath};
+
+    fn success(field: &str, expected: (Option<&str>, &str)) {
+        let (path, label) = FilePath::parse(field).unwrap();
+        assert_eq!(expected, (path.as_ref().map(|p| p.as_str()), label));
+    }
+
+    fn failure(field: &str) {
+        assert!(FilePath::parse(field).is_err());
+    }
+
+    fn roundtrip(path: &str, expected: &str) {
+        let quoted = quote(path);
+        assert_eq!(quoted, expected);
+        assert_eq!(
+            FilePath::parse(&quoted).unwrap().0.unwrap().as_str(),
+            path
+        );
+    }
+
+    #[test]
+    fn case_1() {
+        success("dir/file.txt", (Some("dir/file.txt"), ""));
+    }
+
+    #[test]
+    fn case_2() {
+        success(
+            "dir/my file.txt\t(revision 2)",
+            (Some("dir/my file.txt"), "(revision 2)"),
+        );
+    }
+
+    #[test]
+    fn case_3() {
+        success(
+            "test.txt    (working copy)",
+            (Some("test.txt"), "(working copy)"),
+        );
+    }
+
+    #[test]
+    fn case_4() {
+        success("\"a/dir/f\\303\\274r.txt\"", (Some("a/dir/für.txt"), ""));
+    }
+
+    #[test]
+    fn case_5() {
+        success(
+            "\"a/tab\\there \\\"q\\\"\"\t2024-01-01",
+            (Some("a/tab\there \"q\""), "2024-01-01"),
+        );
+    }
+
+    #[test]
+    fn case_6() {
+        success(
+            "/dev/null\t1970-01-01 00:00:00.000000000 +0000",
+            (None, "1970-01-01 00:00:00.000000000 +0000"),
+        );
+    }
+
+    #[test]
+    fn case_7() {
+        success("./dir//file.txt", (Some("dir/file.txt"), ""));
+    }
+
+    #[test]
+    fn case_8() {
+        failure("\"a/unterminated");
+    }
+
+    #[test]
+    fn case_9() {
+        failure("\"a/bad\\q\"");
+    }
+
+    #[test]
+    fn case_10() {
+        failure("\"a/bad\\377\"");
+    }
+
+    #[test]
+    fn case_11() {
+        roundtrip("dir/my file.txt", "dir/my file.txt");
+    }
+
+    #[test]
+    fn case_12() {
+        roundtrip("dir/für.txt", "\"dir/f\\303\\274r.txt\"");
+    }
+
+    #[test]
+    fn case_13() {
+        roundtrip("a\tb\\c\"d", "\"a\\tb\\\\c\\\"d\"");
+    }
+}
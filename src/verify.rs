@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+
+use crate::apply;
+use crate::diff::Diff;
+use crate::file_diff::FileDiff;
+use crate::hunk;
+use crate::path::FilePath;
+
+// The most base lines a file is rebuilt with, beyond which it is not
+// verified, see verify_file
+const MAX_LINES: usize = 1 << 24;
+
+// A way in which applying the fused diff differs from applying the inputs
+// one after another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub file_name: FilePath,
+    pub line_no: usize, // 1-based, in the file the failure is about
+    pub reason: String,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file_name, self.line_no, self.reason)
+    }
+}
+
+// A line of a file while the inputs are applied: either a line of the base
+// file, known only once a hunk shows it, or one that a hunk added
+#[derive(Debug, Clone)]
+enum Slot {
+    Base(usize),
+    Added(String),
+}
+
+// What is known of the base file, with a placeholder for every line that
+// no hunk shows
+struct Base {
+    _lines: HashMap<usize, String>,
+}
+
+impl Base {
+    fn text(&self, slot: &Slot) -> String {
+        match slot {
+            Slot::Base(index) => match self._lines.get(index) {
+                Some(line) => line.clone(),
+                None => format!("<diff-fuse: base line {}>", index + 1),
+            },
+            Slot::Added(line) => line.clone(),
+        }
+    }
+
+    // Checks a line of a hunk against the slot, which fixes the text of a
+    // base line that no hunk has shown yet
+    fn check(&mut self, slot: &Slot, line: &str) -> bool {
+        match slot {
+            Slot::Base(index) => {
+                let known = self._lines.entry(*index).or_insert(line.into());
+                known.as_str() == line
+            }
+            Slot::Added(added) => added == line,
+        }
+    }
+}
+
+// Applies an input to the slots, like apply::lines but learning the base
+fn apply_input(
+    file_diff: &FileDiff,
+    slots: Vec<Slot>,
+    base: &mut Base,
+) -> Result<Vec<Slot>, Failure> {
+    let failure = |line_no: usize, reason: String| Failure {
+        file_name: file_diff.header().file_name().clone(),
+        line_no,
+        reason,
+    };
+
+    let mut result: Vec<Slot> = Vec::new();
+    let mut cursor = 0usize;
+    for hunk in file_diff.hunks() {
+        let start = (hunk.header().spans()[0][0] - 1).max(0) as usize;
+        if start < cursor || start > slots.len() {
+            return Err(failure(
+                start + 1,
+                format!("Input hunk {} is out of place", hunk.header()),
+            ));
+        }
+        result.extend_from_slice(&slots[cursor..start]);
+
+        let mut index = start;
+        for line in hunk.lines()[1..].iter() {
+            if line.starts_with('\\') {
+                continue;
+            }
+            // an empty line is context that lost its leading space
+            let kind = line.chars().next().unwrap_or(' ');
+            let text = hunk::text(line);
+            if kind == '+' {
+                result.push(Slot::Added(text.to_string()));
+                continue;
+            }
+            let Some(slot) = slots.get(index) else {
+                return Err(failure(
+                    index + 1,
+                    format!("Input hunk {} is past the end", hunk.header()),
+                ));
+            };
+            if !base.check(slot, text) {
+                return Err(failure(
+                    index + 1,
+                    format!(
+                        "Input hunk {} expects '{}', another hunk shows '{}'",
+                        hunk.header(),
+                        text,
+                        base.text(slot)
+                    ),
+                ));
+            }
+            if kind == ' ' {
+                result.push(slot.clone());
+            }
+            index += 1;
+        }
+        cursor = index;
+    }
+
+    result.extend_from_slice(&slots[cursor.min(slots.len())..]);
+    Ok(result)
+}
+
+// Checks one file, given its diffs in the inputs that have it
+fn verify_file(
+    file_name: &FilePath,
+    inputs: &[&FileDiff],
+    fused: Option<&FileDiff>,
+) -> Result<(), Failure> {
+    let failure = |line_no: usize, reason: String| Failure {
+        file_name: file_name.clone(),
+        line_no,
+        reason,
+    };
+
+    // enough base lines for the hunks of every input to fall within, even
+    // after the lines removed by the inputs before
+    let added = inputs
+        .first()
+        .is_some_and(|file_diff| file_diff.header().old_side().path.is_none());
+    let hunks = inputs.iter().flat_map(|file_diff| file_diff.hunks());
+    let num_lines: usize = match added {
+        true => 0,
+        false => {
+            let end = hunks.clone().map(|hunk| hunk.header().spans()[0][1]);
+            let num_removed = hunks.map(|hunk| hunk.header().fields[1]);
+            (end.max().unwrap_or(1) - 1 + num_removed.sum::<i64>()) as usize
+        }
+    };
+    if num_lines > MAX_LINES {
+        return Err(failure(
+            1,
+            format!("The hunks reach line {num_lines}, too far to verify"),
+        ));
+    }
+
+    let mut base = Base {
+        _lines: HashMap::new(),
+    };
+    let mut slots: Vec<Slot> = (0..num_lines).map(Slot::Base).collect();
+    for file_diff in inputs {
+        slots = apply_input(file_diff, slots, &mut base)?;
+    }
+
+    let base_lines: Vec<String> = (0..num_lines)
+        .map(|index| base.text(&Slot::Base(index)))
+        .collect();
+    let expected: Vec<String> =
+        slots.iter().map(|slot| base.text(slot)).collect();
+
+    let actual = match fused {
+        Some(file_diff) => {
+            let (lines, reports) = apply::lines(
+                file_diff,
+                &base_lines,
+                // the fused diff must apply exactly where it says
+                &apply::Options {
+                    max_offset: 0,
+                    ..apply::Options::default()
+                },
+            );
+            if let Some(report) = reports.iter().find(|r| r.result.is_err()) {
+                let line_no = report.header.spans()[0][0].max(1) as usize;
+                return Err(failure(line_no, report.to_string()));
+            }
+            lines
+        }
+        None => base_lines,
+    };
+
+    let line_no = expected
+        .iter()
+        .zip(actual.iter())
+        .position(|(lhs, rhs)| lhs != rhs)
+        .unwrap_or(expected.len().min(actual.len()));
+    match (expected.get(line_no), actual.get(line_no)) {
+        (None, None) => Ok(()),
+        (expected, actual) => Err(failure(
+            line_no + 1,
+            format!(
+                "Expected {} after the inputs, the fused diff gives {}",
+                expected.map_or("the end".into(), |s| format!("'{s}'")),
+                actual.map_or("the end".into(), |s| format!("'{s}'")),
+            ),
+        )),
+    }
+}
+
+// Checks that applying the fused diff has the same effect as applying the
+// inputs in order. The files are rebuilt from what the hunks show of them,
+// with placeholders for the lines that no hunk shows.
+pub fn verify(inputs: &[Diff], fused: &Diff) -> Vec<Failure> {
+    // the diffs of each file in the inputs under its latest name, following
+    // it through renames like Diff::fuse does
+    let mut chains: Vec<(&FilePath, Vec<&FileDiff>)> = Vec::new();
+    for diff in inputs {
+        let file_names = diff.file_names();
+        let keys: Vec<&FilePath> =
+            chains.iter().map(|(key, _)| *key).collect();
+        let mut continued: Vec<&FilePath> = Vec::new();
+        for (key, file_diffs) in chains.iter_mut() {
+            // the same name, or a file renamed from the latest one
+            let new_path = file_diffs.last().and_then(|file_diff| {
+                file_diff.header().new_side().path.as_ref()
+            });
+            let renamed = |next: &&FilePath| {
+                new_path.is_some()
+                    && !keys.contains(next)
+                    && diff.get(next).is_some_and(|file_diff| {
+                        file_diff.header().old_side().path.as_ref() == new_path
+                    })
+            };
+            let next = match file_names.contains(key) {
+                true => Some(*key),
+                false => file_names.iter().copied().find(renamed),
+            };
+            if let Some(next) = next {
+                file_diffs.extend(diff.get(next));
+                continued.push(next);
+                *key = next;
+            }
+        }
+        for key in file_names {
+            if !continued.contains(&key) {
+                chains.push((key, diff.get(key).into_iter().collect()));
+            }
+        }
+    }
+    for key in fused.file_names() {
+        if !chains.iter().any(|(other, _)| *other == key) {
+            chains.push((key, Vec::new()));
+        }
+    }
+
+    // files that could not be fused are kept as the inputs' diffs
+    let failed: Vec<&FilePath> = fused
+        .failed()
+        .iter()
+        .map(|failed| &failed.file_name)
+        .collect();
+    chains.retain(|(key, file_diffs)| {
+        !failed.contains(key)
+            && !file_diffs.iter().any(|file_diff| {
+                failed.contains(&file_diff.header().file_name())
+            })
+    });
+
+    let mut failures: Vec<Failure> = Vec::new();
+    for (file_name, file_diffs) in chains {
+        if let Err(failure) =
+            verify_file(file_name, &file_diffs, fused.get(file_name))
+        {
+            failures.push(failure);
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::verify;
+    use crate::diff::Diff;
+
+    fn file_diff(hunks: &str) -> String {
+        format!(
+            "\
+Index: text.txt
+===================================================================
+--- text.txt
++++ text.txt
+{hunks}"
+        )
+    }
+
+    fn test(inputs: &[&str], fused: &str, expected: &[&str]) {
+        let inputs: Vec<Diff> = inputs
+            .iter()
+            .map(|hunks| file_diff(hunks).parse().unwrap())
+            .collect();
+        let fused: Diff = file_diff(fused).parse().unwrap();
+        let failures: Vec<String> = verify(&inputs, &fused)
+            .iter()
+            .map(|failure| failure.to_string())
+            .collect();
+        assert_eq!(failures, expected);
+    }
+
+    const LEFT: &str = "@@ -1 +1,2 @@\n 1\n+x\n@@ -8 +9 @@\n-8\n+E\n";
+    const RIGHT: &str = "@@ -2 +2 @@\n-x\n+X\n@@ -11 +11 @@\n-10\n+T\n";
+
+    #[test]
+    fn case_1() {
+        test(
+            &[LEFT, RIGHT],
+            "@@ -1 +1,2 @@\n 1\n+X\n@@ -8 +9 @@\n-8\n+E\n@@ -10 +11 @@\n-10\n+T\n",
+            &[],
+        );
+    }
+
+    #[test]
+    fn case_2() {
+        test(
+            &[LEFT, RIGHT],
+            "@@ -1 +1,2 @@\n 1\n+X\n@@ -8 +10 @@\n-8\n+E\n@@ -12 +11 @@\n-10\n+T\n",
+            &["text.txt:12: text.txt: @@ -12 +11 @@: FAILED -- ApplyError: \
+            Could not find the hunk within 0 line(s) of line 12, 0 line(s) of \
+            fuzz: expected '10' at line 12, found '<diff-fuse: base line 12>'"],
+        );
+    }
+
+    #[test]
+    fn case_3() {
+        test(
+            &[LEFT, RIGHT],
+            "@@ -1 +1,2 @@\n 1\n+X\n@@ -8 +9 @@\n-8\n+e\n@@ -10 +11 @@\n-10\n+T\n",
+            &["text.txt:9: Expected 'E' after the inputs, the fused diff \
+            gives 'e'"],
+        );
+    }
+
+    #[test]
+    fn case_4() {
+        test(
+            &[LEFT, "@@ -2 +2 @@\n-y\n+Y\n"],
+            "@@ -1 +1,2 @@\n 1\n+Y\n",
+            &["text.txt:2: Input hunk @@ -2 +2 @@ expects 'y', another hunk \
+            shows 'x'"],
+        );
+    }
+
+    #[test]
+    fn case_5() {
+        // the later input renames the file
+        let left: Diff = "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+-1
++x
+ 2
+"
+        .parse()
+        .unwrap();
+        let right: Diff = "\
+diff --git a/a.txt b/b.txt
+similarity index 50%
+rename from a.txt
+rename to b.txt
+--- a/a.txt
++++ b/b.txt
+@@ -1,2 +1,2 @@
+ x
+-2
++y
+"
+        .parse()
+        .unwrap();
+        let fused = left.clone().fuse(right.clone()).unwrap();
+        assert_eq!(fused.file_names().len(), 1);
+        assert!(verify(&[left, right], &fused).is_empty());
+    }
+}
@@ -0,0 +1,21 @@
+pub mod git;
+pub mod svn;
+
+use crate::diff::Diff;
+use crate::file_diff::FileDiff;
+
+// The lines of every file diff in the diff's order, each written by
+// file_lines
+fn lines<T>(
+    diff: &Diff,
+    options: &T,
+    file_lines: fn(&FileDiff, &T) -> Vec<String>,
+) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for file_name in diff.file_names() {
+        for file_diff in diff.get_all(file_name) {
+            lines.extend(file_lines(file_diff, options));
+        }
+    }
+    lines
+}
@@ -0,0 +1,113 @@
+use crate::diff::Diff;
+use crate::file_diff::FileDiff;
+use crate::path::{quote, FilePath};
+
+const DEFAULT_MODE: &str = "100644";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub old_prefix: String,
+    pub new_prefix: String,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            old_prefix: "a/".to_string(),
+            new_prefix: "b/".to_string(),
+        }
+    }
+}
+
+// Renders the diff as a patch that 'git apply' accepts, whatever format
+// the inputs were in
+pub fn lines(diff: &Diff, options: &Options) -> Vec<String> {
+    super::lines(diff, options, file_lines)
+}
+
+fn path(prefix: &str, path: &FilePath) -> String {
+    quote(&format!("{prefix}{path}"))
+}
+
+// git separates paths with spaces from the rest of '---' and '+++' lines
+// with a tab
+fn label_path(prefix: &str, path: &FilePath) -> String {
+    let quoted = self::path(prefix, path);
+    if quoted.contains(' ') && !quoted.starts_with('"') {
+        quoted + "\t"
+    } else {
+        quoted
+    }
+}
+
+fn file_lines(file_diff: &FileDiff, options: &Options) -> Vec<String> {
+    let header = file_diff.header();
+    let old = header.old_side();
+    let new = header.new_side();
+    let old_path = old.path.as_ref().unwrap_or(header.file_name());
+    let new_path = new.path.as_ref().unwrap_or(header.file_name());
+
+    let mut lines = vec![format!(
+        "diff --git {} {}",
+        path(&options.old_prefix, old_path),
+        path(&options.new_prefix, new_path)
+    )];
+
+    let mut index_mode = None;
+    match (&old.path, &new.path) {
+        (None, None) => {
+            // added and then deleted again
+            return Vec::new();
+        }
+        (None, Some(_)) => lines.push(format!(
+            "new file mode {}",
+            new.mode.as_deref().unwrap_or(DEFAULT_MODE)
+        )),
+        (Some(_), None) => lines.push(format!(
+            "deleted file mode {}",
+            old.mode.as_deref().unwrap_or(DEFAULT_MODE)
+        )),
+        (Some(old_path), Some(new_path)) => {
+            match (&old.mode, &new.mode) {
+                (Some(old_mode), Some(new_mode)) if old_mode != new_mode => {
+                    lines.push(format!("old mode {old_mode}"));
+                    lines.push(format!("new mode {new_mode}"));
+                }
+                _ => index_mode = new.mode.as_ref(),
+            }
+            if old_path != new_path {
+                lines.push(format!("rename from {}", path("", old_path)));
+                lines.push(format!("rename to {}", path("", new_path)));
+            }
+        }
+    }
+
+    if let (Some(old_hash), Some(new_hash)) = (&old.hash, &new.hash) {
+        lines.push(match index_mode {
+            Some(mode) => format!("index {old_hash}..{new_hash} {mode}"),
+            None => format!("index {old_hash}..{new_hash}"),
+        });
+    }
+
+    if file_diff.hunks().is_empty() {
+        return lines;
+    }
+
+    lines.push(match &old.path {
+        Some(old_path) => {
+            format!("--- {}", label_path(&options.old_prefix, old_path))
+        }
+        None => "--- /dev/null".to_string(),
+    });
+    lines.push(match &new.path {
+        Some(new_path) => {
+            format!("+++ {}", label_path(&options.new_prefix, new_path))
+        }
+        None => "+++ /dev/null".to_string(),
+    });
+
+    for hunk in file_diff.hunks() {
+        lines.extend(hunk.lines().iter().cloned());
+    }
+    lines
+}
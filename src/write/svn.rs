@@ -0,0 +1,75 @@
+use crate::diff::Diff;
+use crate::file_diff::FileDiff;
+use crate::header::Side;
+use crate::macros::warnln;
+
+const UNDERLINE: &str =
+    "===================================================================";
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    // revision numbers for the '---' and '+++' labels, which otherwise come
+    // from the inputs if they are svn diffs
+    pub old_revision: Option<String>,
+    pub new_revision: Option<String>,
+}
+
+// Renders the diff in the format of 'svn diff', whatever format the inputs
+// were in. svn diffs have no notion of renames, so a renamed file is
+// written as a change to the new path and a pure rename left out, with a
+// warning either way.
+pub fn lines(diff: &Diff, options: &Options) -> Vec<String> {
+    super::lines(diff, options, file_lines)
+}
+
+// The label of a side, where default is for inputs without an svn label
+fn label(side: &Side, revision: &Option<String>, default: &str) -> String {
+    if side.path.is_none() {
+        return "(nonexistent)".to_string();
+    }
+    if let Some(revision) = revision {
+        return format!("(revision {revision})");
+    }
+    if side.label.starts_with('(') && side.label.ends_with(')') {
+        return side.label.clone();
+    }
+    default.to_string()
+}
+
+fn file_lines(file_diff: &FileDiff, options: &Options) -> Vec<String> {
+    let header = file_diff.header();
+    let old = header.old_side();
+    let new = header.new_side();
+    let path = header.file_name();
+
+    let changed = !file_diff.hunks().is_empty();
+    if let (Some(old_path), Some(new_path)) = (&old.path, &new.path) {
+        if old_path != new_path {
+            let outcome = match changed {
+                true => format!("written as a change to {new_path}"),
+                false => "left out".to_string(),
+            };
+            warnln!(
+                "{old_path} was renamed to {new_path}, which svn diffs can't \
+                express, {outcome}"
+            );
+        }
+    }
+    if !changed && (old.path.is_some() == new.path.is_some()) {
+        // nothing svn would show, e.g. a rename or a mode change
+        return Vec::new();
+    }
+
+    let mut lines = vec![format!("Index: {path}"), UNDERLINE.to_string()];
+    if changed {
+        // the base of a working copy, unless the inputs tell otherwise
+        let old_label = label(old, &options.old_revision, "(revision BASE)");
+        let new_label = label(new, &options.new_revision, "(working copy)");
+        lines.push(format!("--- {path}\t{old_label}"));
+        lines.push(format!("+++ {path}\t{new_label}"));
+        for hunk in file_diff.hunks() {
+            lines.extend(hunk.lines().iter().cloned());
+        }
+    }
+    lines
+}
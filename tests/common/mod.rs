@@ -0,0 +1,46 @@
+// Helpers shared by the integration tests, each of which uses only some
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// A directory under the system's temporary one, which is removed when it
+// goes out of scope, also when the test fails
+pub struct TempDir {
+    _path: PathBuf,
+}
+
+impl TempDir {
+    // The name, the process id and a counter keep the tests that run at
+    // the same time apart
+    pub fn new(name: &str) -> TempDir {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let _path = std::env::temp_dir().join(format!(
+            "diff_fuse_{name}_{}_{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&_path).unwrap();
+        TempDir { _path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self._path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self._path);
+    }
+}
+
+// Writes the files, given by their paths under root and their texts
+pub fn write_tree(root: &Path, files: &[(&str, &str)]) {
+    for (path, text) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+mod common;
+
+#[cfg(test)]
+mod apply {
+    use std::fs;
+    use std::path::Path;
+
+    use super::common::{write_tree, TempDir};
+    use diff_fuse::error::ApplyErrKind;
+    use diff_fuse::{apply, generate};
+
+    fn read_tree(root: &Path, paths: &[&str]) -> Vec<Option<String>> {
+        paths
+            .iter()
+            .map(|path| fs::read_to_string(root.join(path)).ok())
+            .collect()
+    }
+
+    #[test]
+    fn dirs() {
+        let dir = TempDir::new("apply");
+        let root = dir.path();
+        let (old, new, work) =
+            (root.join("old"), root.join("new"), root.join("work"));
+        let old_files = [
+            ("same.txt", "a\n"),
+            ("dir/changed.txt", "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n"),
+            ("removed.txt", "x\ny\n"),
+        ];
+        let new_files = [
+            ("same.txt", "a\n"),
+            ("dir/changed.txt", "A\nb\nc\nd\ne\nf\ng\nh\nI\nj\n"),
+            ("dir/sub/added.txt", "z\n"),
+        ];
+        let paths = [
+            "same.txt",
+            "dir/changed.txt",
+            "removed.txt",
+            "dir/sub/added.txt",
+        ];
+        write_tree(&old, &old_files);
+        write_tree(&new, &new_files);
+        write_tree(&work, &old_files);
+
+        let diff =
+            generate::dirs(&old, &new, &generate::Options::default()).unwrap();
+
+        let mut options = apply::Options {
+            dry_run: true,
+            ..apply::Options::default()
+        };
+        let reports = apply::apply(&diff, &work, &options).unwrap();
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|report| report.result.is_ok()));
+        assert_eq!(read_tree(&work, &paths), read_tree(&old, &paths));
+
+        options.dry_run = false;
+        apply::apply(&diff, &work, &options).unwrap();
+        assert_eq!(read_tree(&work, &paths), read_tree(&new, &paths));
+
+        options.reverse = true;
+        apply::apply(&diff, &work, &options).unwrap();
+        assert_eq!(read_tree(&work, &paths), read_tree(&old, &paths));
+
+        // the file no longer matches the '+' side
+        let reports = apply::apply(&diff, &work, &options).unwrap();
+        assert!(reports.iter().any(|report| report.result.is_err()));
+    }
+
+    #[test]
+    fn line_endings() {
+        let dir = TempDir::new("apply_eol");
+        let root = dir.path();
+        let (old, new, work) =
+            (root.join("old"), root.join("new"), root.join("work"));
+        let old_files = [
+            ("crlf.txt", "a\r\nb\r\nc\r\n"),
+            ("last.txt", "a\nb"),
+            ("added.txt", "a\nb"),
+            ("removed.txt", "a\nb\n"),
+        ];
+        let new_files = [
+            ("crlf.txt", "a\r\nB\r\nc\r\n"),
+            ("last.txt", "A\nb"),
+            ("added.txt", "a\nb\n"),
+            ("removed.txt", "a\nb"),
+        ];
+        let paths = ["crlf.txt", "last.txt", "added.txt", "removed.txt"];
+        write_tree(&old, &old_files);
+        write_tree(&new, &new_files);
+        write_tree(&work, &old_files);
+
+        let diff =
+            generate::dirs(&old, &new, &generate::Options::default()).unwrap();
+        let reports =
+            apply::apply(&diff, &work, &apply::Options::default()).unwrap();
+        assert!(reports.iter().all(|report| report.result.is_ok()));
+        assert_eq!(read_tree(&work, &paths), read_tree(&new, &paths));
+    }
+
+    #[test]
+    fn existing_file() {
+        let dir = TempDir::new("apply_add");
+        let root = dir.path();
+        let (old, new, work) =
+            (root.join("old"), root.join("new"), root.join("work"));
+        write_tree(&new, &[("added.txt", "a\n")]);
+        write_tree(&work, &[("added.txt", "b\n")]);
+        fs::create_dir_all(&old).unwrap();
+
+        let diff =
+            generate::dirs(&old, &new, &generate::Options::default()).unwrap();
+        let reports =
+            apply::apply(&diff, &work, &apply::Options::default()).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(
+            reports[0].result.as_ref().unwrap_err().kind,
+            ApplyErrKind::Exists(work.join("added.txt"))
+        );
+        assert_eq!(
+            read_tree(&work, &["added.txt"]),
+            [Some("b\n".to_string())]
+        );
+    }
+}
@@ -37,11 +37,11 @@ make_diff $initial_commit $final_commit expected.diff
 cargo run -- "$TMP_DIR"/left.diff "$TMP_DIR"/right.diff > "$TMP_DIR"/actual.diff
 
 if [ -s "$TMP_DIR"/actual.diff ]; then
-    patch "$TMP_DIR"/initial.${target##*.} \
-        -i "$TMP_DIR"/actual.diff \
-        -o "$TMP_DIR"/result.${target##*.}
+    mkdir -p "$TMP_DIR"/result/"$(dirname "$target")"
+    cp "$TMP_DIR"/initial.${target##*.} "$TMP_DIR"/result/"$target"
+    cargo run -- apply --dir="$TMP_DIR"/result "$TMP_DIR"/actual.diff
 
-    diff -q "$TMP_DIR"/final.${target##*.} "$TMP_DIR"/result.${target##*.}
+    diff -q "$TMP_DIR"/final.${target##*.} "$TMP_DIR"/result/"$target"
 else
     echo "Empty diff, skipping patch and testing diffs"
     diff -q "$TMP_DIR"/expected.diff "$TMP_DIR"/actual.diff
@@ -0,0 +1,559 @@
+mod common;
+
+#[cfg(test)]
+mod fuse {
+    use diff_fuse::diff::Diff;
+
+    // The svn diff text of the files with their hunks
+    fn text(file_diffs: &[(&str, &str)]) -> String {
+        file_diffs
+            .iter()
+            .map(|(path, hunks)| {
+                format!(
+                    "\
+Index: {path}
+===================================================================
+--- {path}\t(revision 1)
++++ {path}\t(working copy)
+{hunks}"
+                )
+            })
+            .collect()
+    }
+
+    fn diff(file_diffs: &[(&str, &str)]) -> Diff {
+        text(file_diffs).parse().unwrap()
+    }
+
+    mod order {
+        use diff_fuse::diff::{Diff, Order};
+
+        fn file_diff(path: &str, from: &str, to: &str) -> String {
+            format!(
+                "\
+Index: {path}
+===================================================================
+--- {path}\t(revision 1)
++++ {path}\t(working copy)
+@@ -1 +1 @@
+-{from}
++{to}
+"
+            )
+        }
+
+        fn fused(order: Order) -> Vec<String> {
+            let left: String = ["b.txt", "a/z.txt", "c/d/e.txt"]
+                .map(|path| file_diff(path, "1", "2"))
+                .concat();
+            let right: String = ["c/a.txt", "b.txt", "a.txt"]
+                .map(|path| file_diff(path, "2", "3"))
+                .concat();
+
+            let mut left: Diff = left.parse().unwrap();
+            left.sort(order);
+            let diff = left.fuse(right.parse().unwrap()).unwrap();
+            diff.line_iter()
+                .filter_map(|line| line.strip_prefix("Index: "))
+                .map(|line| line.to_string())
+                .collect()
+        }
+
+        #[test]
+        fn first_seen() {
+            assert_eq!(
+                fused(Order::FirstSeen),
+                ["b.txt", "a/z.txt", "c/d/e.txt", "c/a.txt", "a.txt"]
+            );
+        }
+
+        #[test]
+        fn lexicographic() {
+            assert_eq!(
+                fused(Order::Lexicographic),
+                ["a.txt", "a/z.txt", "b.txt", "c/a.txt", "c/d/e.txt"]
+            );
+        }
+
+        #[test]
+        fn tree() {
+            assert_eq!(
+                fused(Order::Tree),
+                ["a/z.txt", "c/d/e.txt", "c/a.txt", "a.txt", "b.txt"]
+            );
+        }
+    }
+
+    mod merge {
+        use std::fs;
+        use std::path::{Path, PathBuf};
+
+        use diff_fuse::diff::Diff;
+        use diff_fuse::generate;
+        use diff_fuse::path::FilePath;
+
+        fn generated(dir: &Path, old: &str, new: &str) -> Diff {
+            let read =
+                |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+            let file_diff = generate::file_diff(
+                &FilePath::new("text.txt"),
+                Some(&read(old)),
+                Some(&read(new)),
+                &generate::Options::default(),
+            )
+            .unwrap();
+            Diff::new(file_diff.into_iter().collect()).unwrap()
+        }
+
+        fn test_impl(dir: PathBuf) {
+            let left = generated(&dir, "initial.txt", "intermediate.txt");
+            let right = generated(&dir, "intermediate.txt", "final.txt");
+            let expected = generated(&dir, "initial.txt", "final.txt");
+
+            // the generator agrees with svn on the hunks
+            let file_name = FilePath::new("text.txt");
+            for (diff, name) in [(&left, "left.diff"), (&right, "right.diff")]
+            {
+                let svn = Diff::read(&dir.join(name)).unwrap();
+                let lines = |diff: &Diff| -> Vec<String> {
+                    let file_diff = diff.get(&file_name).unwrap();
+                    file_diff
+                        .hunks()
+                        .iter()
+                        .flat_map(|hunk| hunk.lines().clone())
+                        .collect()
+                };
+                assert_eq!(lines(diff), lines(&svn));
+            }
+
+            let mut fused = left.clone().fuse(right.clone()).unwrap();
+
+            // either input can be taken out of the fusion again
+            for (mut diff, part) in [
+                (fused.clone().subtract(&right).unwrap(), &left),
+                (fused.clone().subtract_first(&left).unwrap(), &right),
+            ] {
+                diff.rediff();
+                diff.trim_context(3);
+                assert_eq!(diff.to_string(), part.to_string());
+            }
+
+            // both inputs are against the initial text, like two versions
+            // of a patch
+            let mut interdiff =
+                Diff::interdiff(left.clone(), expected.clone()).unwrap();
+            interdiff.trim_context(3);
+            assert_eq!(interdiff.to_string(), right.to_string());
+
+            fused.rediff();
+            fused.trim_context(3);
+            assert_eq!(fused.to_string(), expected.to_string());
+        }
+
+        casegen::for_each_file!("tests/data/svn/merge/success/", test_impl);
+    }
+
+    mod reverse {
+        use std::fs;
+        use std::path::PathBuf;
+
+        use diff_fuse::diff::Diff;
+
+        fn test_impl(diff_path: PathBuf) {
+            let data = fs::read_to_string(diff_path).unwrap();
+            let diff: Diff = data.parse().unwrap();
+            let reversed = diff.clone().reverse();
+            assert_eq!(reversed.clone().reverse().to_string(), data);
+            assert_eq!(diff.fuse(reversed).unwrap().to_string(), "");
+        }
+
+        mod svn {
+            use super::test_impl;
+            use std::path::PathBuf;
+
+            casegen::for_each_file!(
+                "tests/data/svn/parse/success/",
+                test_impl
+            );
+        }
+
+        mod git {
+            use super::test_impl;
+            use std::path::PathBuf;
+
+            casegen::for_each_file!(
+                "tests/data/git/parse/success/",
+                test_impl
+            );
+        }
+
+        mod unified {
+            use super::test_impl;
+            use std::path::PathBuf;
+
+            casegen::for_each_file!(
+                "tests/data/unified/parse/success/",
+                test_impl
+            );
+        }
+    }
+
+    mod subtract {
+        use diff_fuse::diff::Diff;
+
+        fn diff(hunk: &str) -> Diff {
+            format!(
+                "\
+Index: text.txt
+===================================================================
+--- text.txt\t(revision 1)
++++ text.txt\t(working copy)
+{hunk}"
+            )
+            .parse()
+            .unwrap()
+        }
+
+        #[test]
+        fn later() {
+            let fused = diff("@@ -1,2 +1,2 @@\n-a\n-b\n+A\n+B\n");
+            let mut first =
+                fused.subtract(&diff("@@ -2 +2 @@\n-b\n+B\n")).unwrap();
+            first.rediff();
+            assert_eq!(
+                first.to_string(),
+                diff("@@ -1,2 +1,2 @@\n-a\n+A\n b\n").to_string()
+            );
+        }
+
+        #[test]
+        fn first() {
+            let fused = diff("@@ -1,2 +1,2 @@\n-a\n-b\n+A\n+B\n");
+            let mut rest = fused
+                .subtract_first(&diff("@@ -1 +1 @@\n-a\n+A\n"))
+                .unwrap();
+            rest.rediff();
+            assert_eq!(
+                rest.to_string(),
+                diff("@@ -1,2 +1,2 @@\n A\n-b\n+B\n").to_string()
+            );
+        }
+
+        #[test]
+        fn mismatch() {
+            let fused = diff("@@ -1 +1 @@\n-a\n+A\n");
+            let err =
+                fused.subtract(&diff("@@ -1 +1 @@\n-a\n+B\n")).unwrap_err();
+            assert!(err.to_string().contains("Could not subtract the diff"));
+        }
+    }
+
+    mod commute {
+        use super::diff;
+        use diff_fuse::diff::Diff;
+        use diff_fuse::verify;
+
+        #[test]
+        fn independent() {
+            let a = diff(&[
+                ("a.txt", "@@ -1 +1,2 @@\n 1\n+x\n"),
+                ("b.txt", "@@ -2 +2 @@\n-2\n+B\n"),
+            ]);
+            let b = diff(&[
+                ("a.txt", "@@ -8 +8 @@\n-7\n+S\n"),
+                ("c.txt", "@@ -1 +1 @@\n-1\n+C\n"),
+            ]);
+            let (b2, a2) = Diff::commute(a.clone(), b.clone()).unwrap();
+            let fused = a.fuse(b).unwrap();
+            assert!(
+                verify::verify(&[b2.clone(), a2.clone()], &fused).is_empty()
+            );
+            assert_eq!(
+                b2.to_string(),
+                diff(&[
+                    ("a.txt", "@@ -7 +7 @@\n-7\n+S\n"),
+                    ("c.txt", "@@ -1 +1 @@\n-1\n+C\n"),
+                ])
+                .to_string()
+            );
+        }
+
+        #[test]
+        fn dependent() {
+            let a = diff(&[("a.txt", "@@ -1 +1 @@\n-1\n+x\n")]);
+            let b = diff(&[("a.txt", "@@ -1 +1 @@\n-x\n+y\n")]);
+            let err = Diff::commute(a, b).unwrap_err();
+            assert!(err.to_string().contains("touch the same lines"));
+        }
+    }
+
+    mod interdiff {
+        use super::diff;
+        use diff_fuse::diff::Diff;
+
+        #[test]
+        fn files() {
+            let v1 = diff(&[
+                ("same.txt", "@@ -1 +1 @@\n-1\n+S\n"),
+                ("changed.txt", "@@ -1,2 +1,2 @@\n-1\n+A\n 2\n"),
+                ("v1.txt", "@@ -1 +1 @@\n-1\n+X\n"),
+            ]);
+            let v2 = diff(&[
+                ("same.txt", "@@ -1 +1 @@\n-1\n+S\n"),
+                ("changed.txt", "@@ -1,2 +1,2 @@\n-1\n+A\n-2\n+B\n"),
+                ("v2.txt", "@@ -1 +1 @@\n-1\n+Y\n"),
+            ]);
+            let interdiff = Diff::interdiff(v1, v2).unwrap();
+            let file_names: Vec<String> = interdiff
+                .file_names()
+                .iter()
+                .map(|file_name| file_name.to_string())
+                .collect();
+            assert_eq!(file_names, ["changed.txt", "v2.txt", "v1.txt"]);
+
+            let hunks = |file_name: &str| -> Vec<String> {
+                let file_name = diff_fuse::path::FilePath::new(file_name);
+                let file_diff = interdiff.get(&file_name).unwrap();
+                file_diff
+                    .hunks()
+                    .iter()
+                    .flat_map(|hunk| hunk.lines().clone())
+                    .collect()
+            };
+            assert_eq!(
+                hunks("changed.txt"),
+                ["@@ -1,2 +1,2 @@", " A", "-2", "+B"]
+            );
+            assert_eq!(hunks("v1.txt"), ["@@ -1 +1 @@", "-X", "+1"]);
+            assert_eq!(hunks("v2.txt"), ["@@ -1 +1 @@", "-1", "+Y"]);
+        }
+    }
+
+    mod formats {
+        use super::diff;
+        use diff_fuse::diff::Diff;
+
+        #[test]
+        fn svn_then_unified() {
+            let svn = diff(&[("dir/x.txt", "@@ -1,2 +1,2 @@\n-a\n+b\n c\n")]);
+            let unified: Diff = "\
+--- dir/x.txt
++++ dir/x.txt
+@@ -1,2 +1,2 @@
+ b
+-c
++d
+"
+            .parse()
+            .unwrap();
+            let fused = svn.fuse(unified).unwrap();
+            let file_names: Vec<String> = fused
+                .file_names()
+                .iter()
+                .map(|file_name| file_name.to_string())
+                .collect();
+            assert_eq!(file_names, ["dir/x.txt"]);
+            assert_eq!(
+                fused.to_string(),
+                diff(&[("dir/x.txt", "@@ -1,2 +1,2 @@\n-a\n-c\n+b\n+d\n")])
+                    .to_string()
+            );
+        }
+    }
+
+    mod on_failure {
+        use super::{diff, text};
+        use diff_fuse::diff::Diff;
+        use diff_fuse::options::{FuseOptions, OnFailure};
+
+        fn fused(on_failure: OnFailure) -> Result<Diff, String> {
+            let left = diff(&[
+                ("bad.txt", "@@ -1 +1 @@\n-1\n+2\n"),
+                ("good.txt", "@@ -1 +1 @@\n-1\n+2\n"),
+            ]);
+            let right = diff(&[
+                ("bad.txt", "@@ -1 +1 @@\n-x\n+3\n"),
+                ("good.txt", "@@ -1 +1 @@\n-2\n+3\n"),
+            ]);
+            let options = FuseOptions {
+                on_failure,
+                ..FuseOptions::default()
+            };
+            left.fuse_with(right, &options)
+                .map_err(|err| err.to_string())
+        }
+
+        #[test]
+        fn fail() {
+            assert!(fused(OnFailure::Fail).is_err());
+        }
+
+        #[test]
+        fn sequential() {
+            let result = fused(OnFailure::Sequential).unwrap();
+            assert_eq!(result.failed().len(), 1);
+            assert_eq!(result.failed()[0].file_name.as_str(), "bad.txt");
+            assert_eq!(
+                result.to_string(),
+                text(&[
+                    ("bad.txt", "@@ -1 +1 @@\n-1\n+2\n"),
+                    ("bad.txt", "@@ -1 +1 @@\n-x\n+3\n"),
+                    ("good.txt", "@@ -1 +1 @@\n-1\n+3\n"),
+                ])
+            );
+        }
+
+        #[test]
+        fn omit() {
+            let result = fused(OnFailure::Omit).unwrap();
+            assert_eq!(result.failed().len(), 1);
+            assert_eq!(
+                result.to_string(),
+                text(&[("good.txt", "@@ -1 +1 @@\n-1\n+3\n")])
+            );
+        }
+    }
+
+    mod matching {
+        use std::fs;
+
+        use super::super::common::TempDir;
+        use diff_fuse::diff::Diff;
+        use diff_fuse::file_diff::{LooseMatch, Side};
+        use diff_fuse::options::{FuseOptions, Matching};
+
+        fn diff(hunks: &str) -> Diff {
+            format!("--- a.txt\n+++ a.txt\n{hunks}").parse().unwrap()
+        }
+
+        fn fused(left: &str, right: &str, matching: Matching) -> Option<Diff> {
+            let options = FuseOptions {
+                matching,
+                ..FuseOptions::default()
+            };
+            diff(left).fuse_with(diff(right), &options).ok()
+        }
+
+        #[test]
+        fn exact() {
+            let left = "@@ -1,2 +1,2 @@\n-a\n+b \n c\n";
+            let right = "@@ -1,2 +1,2 @@\n b\n-c\n+C\n";
+            assert!(fused(left, right, Matching::Exact).is_none());
+            assert!(fused(left, right, Matching::IgnoreEol).is_none());
+        }
+
+        #[test]
+        fn trailing_space() {
+            let left = "@@ -1,2 +1,2 @@\n-a\n+b \n c\n";
+            let right = "@@ -1,2 +1,2 @@\n b\n-c\n+C\n";
+            let result =
+                fused(left, right, Matching::IgnoreTrailingSpace).unwrap();
+            assert_eq!(
+                result.to_string(),
+                diff("@@ -1,2 +1,2 @@\n-a\n-c\n+b\n+C\n").to_string()
+            );
+            let loose_matches = result.loose_matches();
+            assert_eq!(loose_matches.len(), 1);
+            assert_eq!(loose_matches[0].0.as_str(), "a.txt");
+            assert_eq!(
+                loose_matches[0].1,
+                &LooseMatch {
+                    left: "+b ".to_string(),
+                    right: " b".to_string(),
+                    kept: Some(Side::Right),
+                }
+            );
+        }
+
+        #[test]
+        fn all_space() {
+            // the later diff was made with the re-indentation ignored
+            let left = "@@ -1,2 +1,2 @@\n-\tx = 1\n+    x = 1\n y\n";
+            let right = "@@ -1,2 +1,2 @@\n \tx = 1\n-y\n+z\n";
+            assert!(
+                fused(left, right, Matching::IgnoreTrailingSpace).is_none()
+            );
+            let result = fused(left, right, Matching::IgnoreAllSpace).unwrap();
+            assert_eq!(
+                result.to_string(),
+                diff("@@ -1,2 +1,2 @@\n-\tx = 1\n-y\n+\tx = 1\n+z\n")
+                    .to_string()
+            );
+        }
+
+        #[test]
+        fn eol() {
+            // context that the base has, kept as the earlier diff has it
+            let left = "@@ -1,2 +1,2 @@\n a\r\n-b\n+B\n";
+            let right = "@@ -1,2 +1,2 @@\n-a\n+A\n B\n";
+            assert!(fused(left, right, Matching::Exact).is_none());
+            let result = fused(left, right, Matching::IgnoreEol).unwrap();
+            assert_eq!(
+                result.to_string(),
+                diff("@@ -1,2 +1,2 @@\n-a\r\n-b\n+A\n+B\n").to_string()
+            );
+            assert_eq!(result.loose_matches()[0].1.kept, Some(Side::Left));
+        }
+
+        #[test]
+        fn eol_read() {
+            // a file with CRLF line endings, which the diffs keep
+            let dir = TempDir::new("matching");
+            let [left, right] = [
+                (
+                    "left.diff",
+                    "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n a\r\n-b\r\n+B\r\n",
+                ),
+                (
+                    "right.diff",
+                    "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n-a\n+A\n B\r\n",
+                ),
+            ]
+            .map(|(name, text)| {
+                let path = dir.path().join(name);
+                fs::write(&path, text).unwrap();
+                Diff::read(&path).unwrap()
+            });
+
+            let options = FuseOptions {
+                matching: Matching::IgnoreEol,
+                ..FuseOptions::default()
+            };
+            let result = left.fuse_with(right, &options).unwrap();
+            assert_eq!(
+                result.to_string(),
+                "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n\
+                -a\r\n-b\r\n+A\n+B\r\n"
+            );
+        }
+    }
+
+    mod errors {
+        use diff_fuse::diff::Diff;
+        use diff_fuse::error::MergeErrKind;
+        use diff_fuse::hunk::Header;
+
+        fn diff(hunks: &str) -> Diff {
+            format!("--- a.txt\n+++ a.txt\n{hunks}").parse().unwrap()
+        }
+
+        #[test]
+        fn context_mismatch() {
+            let err = diff("@@ -1,2 +1,2 @@\n-a\n+b\n c\n")
+                .fuse(diff("@@ -1,2 +1,2 @@\n-x\n+y\n c\n"))
+                .unwrap_err();
+            assert_eq!(
+                err.kind,
+                MergeErrKind::ContextMismatch {
+                    left: "+b".to_string(),
+                    right: "-x".to_string()
+                }
+            );
+            assert_eq!(err.location.file_name.unwrap().to_string(), "a.txt");
+            assert_eq!(
+                err.location.headers,
+                vec![Header::from([1, 2, 1, 2]), Header::from([1, 2, 1, 2])]
+            );
+        }
+    }
+}
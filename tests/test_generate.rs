@@ -0,0 +1,129 @@
+mod common;
+
+#[cfg(test)]
+mod generate {
+    use super::common::{write_tree, TempDir};
+    use diff_fuse::diff::Diff;
+    use diff_fuse::generate;
+
+    #[test]
+    fn dirs() {
+        let dir = TempDir::new("generate");
+        let root = dir.path();
+        let (old, new) = (root.join("old"), root.join("new"));
+        write_tree(
+            &old,
+            &[
+                ("same.txt", "a\n"),
+                ("dir/changed.txt", "a\nb\nc\nd\ne\nf\ng\nh\n"),
+                ("removed.txt", "x\ny\n"),
+            ],
+        );
+        write_tree(
+            &new,
+            &[
+                ("same.txt", "a\n"),
+                ("dir/changed.txt", "a\nb\nc\nd\nE\nf\ng\nh\n"),
+                ("dir/sub/added.txt", "z\n"),
+            ],
+        );
+
+        let options = generate::Options {
+            context: 2,
+            ..generate::Options::default()
+        };
+        let diff = generate::dirs(&old, &new, &options).unwrap();
+
+        assert_eq!(
+            diff.to_string(),
+            "\
+Index: dir/changed.txt
+===================================================================
+--- dir/changed.txt\t(revision 1)
++++ dir/changed.txt\t(working copy)
+@@ -3,5 +3,5 @@
+ c
+ d
+-e
++E
+ f
+ g
+Index: dir/sub/added.txt
+===================================================================
+--- dir/sub/added.txt\t(nonexistent)
++++ dir/sub/added.txt\t(working copy)
+@@ -0,0 +1 @@
++z
+Index: removed.txt
+===================================================================
+--- removed.txt\t(revision 1)
++++ removed.txt\t(nonexistent)
+@@ -1,2 +0,0 @@
+-x
+-y
+"
+        );
+    }
+
+    #[test]
+    fn final_newline() {
+        let dir = TempDir::new("generate_eol");
+        let root = dir.path();
+        let (old, new) = (root.join("old"), root.join("new"));
+        write_tree(
+            &old,
+            &[
+                ("added.txt", "a\nb"),
+                ("changed.txt", "a\nb"),
+                ("removed.txt", "a\nb\n"),
+            ],
+        );
+        write_tree(
+            &new,
+            &[
+                ("added.txt", "a\nb\n"),
+                ("changed.txt", "A\nb"),
+                ("removed.txt", "a\nb"),
+            ],
+        );
+
+        let diff =
+            generate::dirs(&old, &new, &generate::Options::default()).unwrap();
+
+        assert_eq!(
+            diff.to_string(),
+            "\
+Index: added.txt
+===================================================================
+--- added.txt\t(revision 1)
++++ added.txt\t(working copy)
+@@ -1,2 +1,2 @@
+ a
+-b
+\\ No newline at end of file
++b
+Index: changed.txt
+===================================================================
+--- changed.txt\t(revision 1)
++++ changed.txt\t(working copy)
+@@ -1,2 +1,2 @@
+-a
++A
+ b
+\\ No newline at end of file
+Index: removed.txt
+===================================================================
+--- removed.txt\t(revision 1)
++++ removed.txt\t(working copy)
+@@ -1,2 +1,2 @@
+ a
+-b
++b
+\\ No newline at end of file
+"
+        );
+        // and reads back as it is
+        let text = diff.to_string();
+        assert_eq!(text.parse::<Diff>().unwrap().to_string(), text);
+    }
+}
@@ -1,17 +1,274 @@
 #[cfg(test)]
 mod parse {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use diff_fuse::diff::Diff;
+
+    fn test_impl(diff_path: PathBuf) {
+        let data = fs::read_to_string(diff_path).unwrap();
+        let diff: Diff = data.parse().unwrap();
+        assert_eq!(data, diff.to_string());
+    }
+
     mod success {
+        use super::test_impl;
+        use std::path::PathBuf;
+
+        casegen::for_each_file!("tests/data/svn/parse/success/", test_impl);
+    }
+
+    mod git_success {
+        use super::test_impl;
+        use std::path::PathBuf;
+
+        casegen::for_each_file!("tests/data/git/parse/success/", test_impl);
+    }
+
+    mod unified_success {
+        use super::test_impl;
+        use std::path::PathBuf;
+
+        casegen::for_each_file!(
+            "tests/data/unified/parse/success/",
+            test_impl
+        );
+    }
+
+    mod lenient {
         use std::fs;
         use std::path::PathBuf;
 
         use diff_fuse::diff::Diff;
+        use diff_fuse::options::ParseOptions;
+
+        fn test_impl(case_dir: PathBuf) {
+            let input_path = case_dir.join("input.diff");
+            let expected = fs::read_to_string(case_dir.join("expected.diff"));
 
-        fn test_impl(diff_path: PathBuf) {
-            let data = fs::read_to_string(diff_path).unwrap();
-            let diff = Diff::from(data.parse().unwrap());
-            assert_eq!(data, diff.to_string());
+            let options = ParseOptions {
+                lenient: true,
+                ..ParseOptions::default()
+            };
+            assert!(Diff::read(&input_path).is_err());
+
+            let diff = Diff::read_with(&input_path, &options).unwrap();
+            assert!(!diff.skipped().is_empty());
+            assert_eq!(expected.unwrap(), diff.to_string());
         }
 
-        casegen::for_each_file!("tests/data/svn/parse/success/", test_impl);
+        casegen::for_each_file!("tests/data/svn/parse/lenient/", test_impl);
+    }
+
+    mod paths {
+        use diff_fuse::diff::Diff;
+
+        fn file_names(data: &str) -> Vec<String> {
+            let diff: Diff = data.parse().unwrap();
+            diff.file_names().iter().map(|p| p.to_string()).collect()
+        }
+
+        #[test]
+        fn case_1() {
+            let svn = "\
+Index: dir/für.txt
+===================================================================
+--- dir/für.txt\t(revision 2)
++++ dir/für.txt\t(working copy)
+@@ -1 +1 @@
+-a
++b
+";
+            let git = "\
+diff --git \"a/dir/f\\303\\274r.txt\" \"b/dir/f\\303\\274r.txt\"
+index 7898192..6178079 100644
+--- \"a/dir/f\\303\\274r.txt\"
++++ \"b/dir/f\\303\\274r.txt\"
+@@ -1 +1 @@
+-b
++c
+";
+            let unified = "\
+--- old/dir/für.txt\t2024-01-01 10:00:00.000000000 +0000
++++ new/dir/für.txt\t2024-01-02 10:00:00.000000000 +0000
+@@ -1 +1 @@
+-c
++d
+";
+            assert_eq!(file_names(svn), vec!["dir/für.txt"]);
+            assert_eq!(file_names(git), vec!["dir/für.txt"]);
+            assert_eq!(file_names(unified), vec!["dir/für.txt"]);
+
+            let fused = [svn, git, unified]
+                .map(|data| data.parse::<Diff>().unwrap())
+                .into_iter()
+                .reduce(|lhs, rhs| lhs.fuse(rhs).unwrap())
+                .unwrap();
+            assert_eq!(fused.file_names().len(), 1);
+            assert!(fused.to_string().ends_with("@@ -1 +1 @@\n-a\n+d\n"));
+        }
+
+        #[test]
+        fn case_2() {
+            let git = "\
+diff --git a/my file.txt b/my file.txt
+new file mode 100644
+--- /dev/null
++++ b/my file.txt\t
+@@ -0,0 +1 @@
++a
+diff --git a/old name.txt b/new name.txt
+similarity index 100%
+rename from old name.txt
+rename to new name.txt
+";
+            assert_eq!(file_names(git), vec!["my file.txt", "new name.txt"]);
+        }
+
+        #[test]
+        fn case_3() {
+            let added = "\
+--- /dev/null
++++ b/x.txt
+@@ -0,0 +1,2 @@
++a
++b
+";
+            let edited = "\
+--- a/x.txt
++++ b/x.txt
+@@ -1,2 +1,2 @@
+ a
+-b
++c
+";
+            assert_eq!(file_names(added), vec!["x.txt"]);
+            let fused = added
+                .parse::<Diff>()
+                .unwrap()
+                .fuse(edited.parse().unwrap())
+                .unwrap();
+            assert_eq!(fused.file_names().len(), 1);
+            assert!(fused.to_string().ends_with("@@ -0,0 +1,2 @@\n+a\n+c\n"));
+        }
+    }
+
+    mod errors {
+        use diff_fuse::diff::Diff;
+        use diff_fuse::error::{ParseErr, ParseErrKind};
+        use diff_fuse::hunk::Header;
+
+        fn parse_err(data: &str) -> ParseErr {
+            data.parse::<Diff>().unwrap_err()
+        }
+
+        #[test]
+        fn count_mismatch() {
+            let err = parse_err(
+                "--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-a\n+b\n\
+                @@ -5,3 +5,3 @@\n-x\n+y\n z\n",
+            );
+            assert_eq!(
+                err.kind,
+                ParseErrKind::CountMismatch { counts: (2, 2) }
+            );
+            assert_eq!(err.location.line_no, Some(6));
+            assert_eq!(err.location.file_name.unwrap().to_string(), "a.txt");
+            assert_eq!(err.location.headers, vec![Header::from([5, 3, 5, 3])]);
+        }
+
+        #[test]
+        fn bad_header() {
+            let err =
+                parse_err("--- a.txt\n+++ a.txt\n@@ -1 +1 x@@\n-a\n+b\n");
+            assert!(matches!(err.kind, ParseErrKind::BadHeader(_)));
+            assert_eq!(err.location.line_no, Some(3));
+            assert!(err.to_string().ends_with("\n     3 | @@ -1 +1 x@@"));
+
+            let err = parse_err("Index: a.txt\n---\n");
+            assert!(matches!(err.kind, ParseErrKind::BadHeader(_)));
+            assert_eq!(err.location.line_no, Some(2));
+        }
+
+        #[test]
+        fn large_header() {
+            let err = parse_err(
+                "--- a.txt\n+++ a.txt\n@@ -9223372036854775807,1 +1 @@\n\
+                -a\n+b\n@@ -9223372036854775806,1 +2 @@\n-b\n+c\n",
+            );
+            assert!(matches!(err.kind, ParseErrKind::BadHeader(_)));
+            assert_eq!(err.location.line_no, Some(3));
+        }
+
+        #[test]
+        fn overlap() {
+            let err = parse_err(
+                "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n-a\n-b\n+A\n+B\n\
+                @@ -2 +4 @@\n-b\n+x\n",
+            );
+            assert_eq!(err.kind, ParseErrKind::Overlap);
+            assert_eq!(err.location.line_no, Some(8));
+            assert_eq!(
+                err.location.headers,
+                vec![Header::from([1, 2, 1, 2]), Header::from([2, 1, 4, 1])]
+            );
+        }
+
+        #[test]
+        fn repeated_lines() {
+            // the next hunk has the same header as the offending one
+            let err = parse_err(
+                "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n-a\n+b\n\
+                @@ -1,2 +1,2 @@\n-a\n-b\n+c\n+d\n",
+            );
+            assert_eq!(
+                err.kind,
+                ParseErrKind::CountMismatch { counts: (1, 1) }
+            );
+            assert_eq!(err.location.line_no, Some(3));
+
+            // a hunk repeated with a different '+' line
+            let err = parse_err(
+                "--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-a\n+b\n\
+                @@ -1 +1 @@\n-a\n+c\n",
+            );
+            assert!(matches!(err.kind, ParseErrKind::Merge(_)));
+            assert_eq!(err.location.line_no, Some(6));
+            assert!(err.to_string().ends_with("\n     6 | @@ -1 +1 @@"));
+        }
+
+        #[test]
+        fn unreadable_base() {
+            let mut diff: Diff = "\
+--- b.txt
++++ b.txt
+@@ -1 +1 @@
+-a
++b
+--- a.txt
++++ a.txt
+@@ -1 +1 @@
+-a
++b
+"
+            .parse()
+            .unwrap();
+            let base_dir = std::env::temp_dir().join("diff_fuse_no_base");
+            let err = diff.expand_context(&base_dir, 3).unwrap_err();
+            assert!(matches!(
+                err.kind,
+                ParseErrKind::BaseUnreadable { path, .. }
+                    if path == base_dir.join("b.txt")
+            ));
+            assert_eq!(err.location.file_name.unwrap().to_string(), "b.txt");
+        }
+
+        #[test]
+        fn multiple_blocks() {
+            let block = "--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-a\n+b\n";
+            let err = parse_err(&format!("{block}{block}"));
+            assert_eq!(err.kind, ParseErrKind::MultipleBlocks);
+            assert_eq!(err.location.line_no, Some(6));
+        }
     }
 }
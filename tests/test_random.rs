@@ -0,0 +1,348 @@
+#[cfg(test)]
+mod random {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use diff_fuse::apply;
+    use diff_fuse::diff::Diff;
+    use diff_fuse::generate;
+    use diff_fuse::hunk::{Header, Hunk};
+    use diff_fuse::options::{FuseOptions, Matching, OnFailure, ParseOptions};
+    use diff_fuse::path::FilePath;
+    use diff_fuse::verify;
+    use diff_fuse::write;
+
+    const NUM_CASES: u64 = 500;
+
+    // xorshift64*, which is plenty for generating test inputs
+    struct Rng(u64);
+
+    impl Rng {
+        fn new(seed: u64) -> Rng {
+            Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545f4914f6cdd1d)
+        }
+
+        fn below(&mut self, num: usize) -> usize {
+            (self.next() % num.max(1) as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
+    }
+
+    // Runs the check on the input, naming the case if it panics
+    fn check<T: std::fmt::Debug>(case: u64, input: &T, f: impl FnOnce()) {
+        if catch_unwind(AssertUnwindSafe(f)).is_err() {
+            panic!("Case {case} panicked on {input:?}");
+        }
+    }
+
+    // Text made of bits of diff syntax and arbitrary bytes
+    fn text(rng: &mut Rng) -> String {
+        const PIECES: [&str; 27] = [
+            "@@ ",
+            " @@",
+            "-",
+            "+",
+            " ",
+            "\n",
+            "\n",
+            "\n",
+            "\t",
+            ",",
+            "1",
+            "0",
+            "9999999999999999999",
+            "9223372036854775807",
+            "2147483647",
+            "2147483646",
+            "Index: ",
+            "diff --git ",
+            "--- ",
+            "+++ ",
+            "a/",
+            "b/",
+            "é",
+            "\"",
+            "\\",
+            "=====",
+            "rename from ",
+        ];
+        let mut bytes: Vec<u8> = Vec::new();
+        for _ in 0..rng.below(200) {
+            if rng.below(8) == 0 {
+                bytes.push(rng.next() as u8);
+            } else {
+                bytes.extend(rng.pick(&PIECES).as_bytes());
+            }
+        }
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    // The diffs are made of the joined lines, which lose empty last lines
+    fn trimmed(mut lines: Vec<String>) -> Vec<String> {
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+
+    // A file of short lines, some of which repeat
+    fn file(rng: &mut Rng) -> Vec<String> {
+        const LINES: [&str; 6] = ["a", "b", "c", "", "é", " x "];
+        trimmed(
+            (0..rng.below(12))
+                .map(|_| rng.pick(&LINES).to_string())
+                .collect(),
+        )
+    }
+
+    // The file with some of its lines replaced, dropped or added
+    fn edit(rng: &mut Rng, lines: &[String]) -> Vec<String> {
+        let mut result: Vec<String> = Vec::new();
+        for line in lines {
+            match rng.below(6) {
+                0 => {}
+                1 => result.push(format!("{line}{}", rng.below(9))),
+                2 => {
+                    result.push(line.clone());
+                    result.push(rng.pick(&["d", "é", ""]).to_string());
+                }
+                _ => result.push(line.clone()),
+            }
+        }
+        trimmed(result)
+    }
+
+    fn diff(old: &[String], new: &[String], context: usize) -> Diff {
+        let options = generate::Options {
+            context,
+            ..generate::Options::default()
+        };
+        let old = old.join("\n");
+        let new = new.join("\n");
+        let file_name = FilePath::new("f.txt");
+        let file_diff =
+            generate::file_diff(&file_name, Some(&old), Some(&new), &options)
+                .unwrap();
+        Diff::new(file_diff.into_iter().collect()).unwrap()
+    }
+
+    // Lines of the text with a few of them dropped, repeated or cut short
+    fn mangle(rng: &mut Rng, text: &str) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for line in text.lines() {
+            match rng.below(12) {
+                0 => {}
+                1 => lines.extend([line.to_string(), line.to_string()]),
+                2 => lines.push(line.chars().take(rng.below(4)).collect()),
+                3 => lines.push(format!("{}{line}", rng.pick(&["-", "+"]))),
+                _ => lines.push(line.to_string()),
+            }
+        }
+        lines.join("\n")
+    }
+
+    // Everything that can be done with one or two diffs, which may fail
+    // but must not panic
+    fn exercise(lhs: &Diff, rhs: &Diff) {
+        let _ = lhs.to_string();
+        let _ = write::git::lines(lhs, &write::git::Options::default());
+        let _ = write::svn::lines(lhs, &write::svn::Options::default());
+        let _ = lhs.clone().reverse().to_string();
+
+        let mut copy = lhs.clone();
+        copy.rediff();
+        copy.trim_context(1);
+        let _ = copy.recount();
+
+        for on_failure in [OnFailure::Fail, OnFailure::Sequential] {
+            for conflicts in [false, true] {
+                let options = FuseOptions {
+                    conflicts,
+                    on_failure,
+                    matching: match conflicts {
+                        false => Matching::IgnoreAllSpace,
+                        true => Matching::Exact,
+                    },
+                };
+                if let Ok(fused) = lhs.clone().fuse_with(rhs.clone(), &options)
+                {
+                    let _ = fused.to_string();
+                    let _ =
+                        verify::verify(&[lhs.clone(), rhs.clone()], &fused);
+                    let _ = fused.clone().subtract(rhs);
+                    let _ = fused.subtract_first(lhs);
+                }
+            }
+        }
+        let _ = Diff::interdiff(lhs.clone(), rhs.clone());
+        let _ = Diff::commute(lhs.clone(), rhs.clone());
+
+        for file_name in lhs.file_names() {
+            let Some(file_diff) = lhs.get(file_name) else {
+                continue;
+            };
+            let text = ["a", "", "é", "b"];
+            for reverse in [false, true] {
+                let options = apply::Options {
+                    reverse,
+                    max_offset: 2,
+                    fuzz: 1,
+                    conflicts: true,
+                    ..apply::Options::default()
+                };
+                let _ = apply::lines(file_diff, &text, &options);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_text() {
+        let mut rng = Rng::new(1);
+        for case in 0..NUM_CASES * 4 {
+            let input = text(&mut rng);
+            check(case, &input, || {
+                for (lenient, recount) in
+                    [(false, false), (true, false), (true, true)]
+                {
+                    let options = ParseOptions { lenient, recount };
+                    let diff =
+                        Diff::from_lines_with(&mut input.lines(), &options);
+                    if let Ok(diff) = diff {
+                        exercise(&diff, &diff);
+                    }
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn fuse_generated() {
+        let mut rng = Rng::new(2);
+        for case in 0..NUM_CASES {
+            let v0 = file(&mut rng);
+            let v1 = edit(&mut rng, &v0);
+            let v2 = edit(&mut rng, &v1);
+            check(case, &(&v0, &v1, &v2), || {
+                let context = rng.below(4);
+                let lhs = diff(&v0, &v1, context);
+                let rhs = diff(&v1, &v2, rng.below(4));
+                let fused = lhs.clone().fuse(rhs.clone()).unwrap();
+                assert!(verify::verify(&[lhs.clone(), rhs.clone()], &fused)
+                    .is_empty());
+                exercise(&lhs, &rhs);
+
+                let expected = diff(&v0, &v2, context);
+                if let Some(file_diff) = fused.get(&FilePath::new("f.txt")) {
+                    let (lines, reports) = apply::lines(
+                        file_diff,
+                        &v0,
+                        &apply::Options::default(),
+                    );
+                    assert!(reports
+                        .iter()
+                        .all(|report| report.result.is_ok()));
+                    assert_eq!(lines, v2);
+                } else {
+                    assert!(expected.file_names().is_empty());
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn fuse_mangled() {
+        let mut rng = Rng::new(3);
+        for case in 0..NUM_CASES {
+            let v0 = file(&mut rng);
+            let v1 = edit(&mut rng, &v0);
+            let v2 = edit(&mut rng, &v1);
+            let lhs = mangle(&mut rng, &diff(&v0, &v1, 2).to_string());
+            let rhs = mangle(&mut rng, &diff(&v1, &v2, 1).to_string());
+            check(case, &(&lhs, &rhs), || {
+                let options = ParseOptions {
+                    lenient: true,
+                    recount: rng.below(2) == 0,
+                };
+                let parse = |data: &str| {
+                    Diff::from_lines_with(&mut data.lines(), &options)
+                };
+                if let (Ok(lhs), Ok(rhs)) = (parse(&lhs), parse(&rhs)) {
+                    exercise(&lhs, &rhs);
+                    exercise(&rhs, &lhs);
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn large_headers() {
+        let mut rng = Rng::new(5);
+        const NUMBERS: [&str; 6] = [
+            "9223372036854775807",
+            "9223372036854775806",
+            "2147483647",
+            "2147483646",
+            "1",
+            "0",
+        ];
+        for case in 0..NUM_CASES {
+            let mut input = String::from("--- a.txt\n+++ a.txt\n");
+            for _ in 0..rng.below(4) {
+                input += &format!(
+                    "@@ -{},{} +{},{} @@\n-a\n+b\n",
+                    rng.pick(&NUMBERS),
+                    rng.pick(&["1", "0"]),
+                    rng.pick(&NUMBERS),
+                    rng.pick(&["1", "0"]),
+                );
+            }
+            check(case, &input, || {
+                for (lenient, recount) in [(false, false), (true, true)] {
+                    let options = ParseOptions { lenient, recount };
+                    let diff =
+                        Diff::from_lines_with(&mut input.lines(), &options);
+                    if let Ok(diff) = diff {
+                        exercise(&diff, &diff);
+                    }
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn hunks() {
+        let mut rng = Rng::new(4);
+        const LINES: [&str; 8] = ["-a", "+a", " a", "", "é", "-é", "+", "\\"];
+        let hunk = |rng: &mut Rng| {
+            let header: Header = [0; 4].map(|_| rng.below(5) as i64).into();
+            let lines = (0..rng.below(6))
+                .map(|_| rng.pick(&LINES).to_string())
+                .collect();
+            Hunk::new(header, lines)
+        };
+        for case in 0..NUM_CASES * 4 {
+            let lhs = hunk(&mut rng);
+            let rhs = hunk(&mut rng);
+            check(case, &(&lhs, &rhs), || {
+                let _ = lhs.clone().fuse(rhs.clone());
+                let _ = lhs.clone().merge(rhs.clone());
+                let _ = lhs.clone().join(rhs.clone());
+                let _ = lhs.clone().reverse().to_string();
+                let _ = lhs.clone().trim_context(1);
+                let _ = lhs.clone().recount(rng.below(3) as i64 - 1);
+                let _ = lhs.clone().rediff();
+                let base: Vec<String> = file(&mut Rng::new(case));
+                let _ = lhs.clone().expand_context(&base, [1, 1]);
+            });
+        }
+    }
+}
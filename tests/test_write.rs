@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod write {
+    mod git {
+        use std::fs;
+        use std::path::PathBuf;
+
+        use diff_fuse::diff::Diff;
+        use diff_fuse::write::git::{lines, Options};
+
+        fn test_impl(case_dir: PathBuf) {
+            let diff = Diff::read(&case_dir.join("input.diff")).unwrap();
+            let expected = fs::read_to_string(case_dir.join("expected.diff"));
+            let actual: String = lines(&diff, &Options::default())
+                .into_iter()
+                .map(|line| line + "\n")
+                .collect();
+            assert_eq!(expected.unwrap(), actual);
+        }
+
+        casegen::for_each_file!("tests/data/git/write/success/", test_impl);
+
+        #[test]
+        fn prefixes() {
+            let diff: Diff = "\
+Index: test.txt
+===================================================================
+--- test.txt\t(revision 2)
++++ test.txt\t(working copy)
+@@ -1 +1 @@
+-a
++b
+"
+            .parse()
+            .unwrap();
+            let options = Options {
+                old_prefix: "old/".to_string(),
+                new_prefix: String::new(),
+            };
+            assert_eq!(
+                lines(&diff, &options)[..3],
+                [
+                    "diff --git old/test.txt test.txt",
+                    "--- old/test.txt",
+                    "+++ test.txt"
+                ]
+            );
+        }
+    }
+
+    mod svn {
+        use std::fs;
+        use std::path::PathBuf;
+
+        use diff_fuse::diff::Diff;
+        use diff_fuse::write::svn::{lines, Options};
+
+        fn test_impl(case_dir: PathBuf) {
+            let diff = Diff::read(&case_dir.join("input.diff")).unwrap();
+            let expected = fs::read_to_string(case_dir.join("expected.diff"));
+            let actual: String = lines(&diff, &Options::default())
+                .into_iter()
+                .map(|line| line + "\n")
+                .collect();
+            assert_eq!(expected.unwrap(), actual);
+        }
+
+        casegen::for_each_file!("tests/data/svn/write/success/", test_impl);
+
+        #[test]
+        fn revisions() {
+            let diff: Diff = "\
+Index: test.txt
+===================================================================
+--- test.txt\t(revision 2)
++++ test.txt\t(working copy)
+@@ -1 +1 @@
+-a
++b
+"
+            .parse()
+            .unwrap();
+            assert_eq!(
+                lines(&diff, &Options::default())[2..4],
+                ["--- test.txt\t(revision 2)", "+++ test.txt\t(working copy)"]
+            );
+
+            let options = Options {
+                old_revision: Some("10".to_string()),
+                new_revision: Some("11".to_string()),
+            };
+            assert_eq!(
+                lines(&diff, &options)[2..4],
+                ["--- test.txt\t(revision 10)", "+++ test.txt\t(revision 11)"]
+            );
+        }
+    }
+}